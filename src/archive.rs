@@ -0,0 +1,245 @@
+//! Everything the game saves, bundled into one file for moving it to
+//! another machine and back.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::runlog::{self, RUN_LOG_FILE};
+use crate::scores::{HighScores, SCORES_FILE};
+use crate::tunables::{Tunables, TUNABLES_FILE};
+
+/// Where archives are written and read from, next to the game.
+pub const ARCHIVE_FILE: &str = "onesys-save.ron";
+const ARCHIVE_TEMP_FILE: &str = "onesys-save.ron.tmp";
+// Bumped whenever what's in an archive changes.
+const ARCHIVE_VERSION: u32 = 1;
+
+/// The saved files an archive carries, each as the text it was saved
+/// as, or `None` if there wasn't one.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SaveData {
+    pub high_scores: Option<String>,
+    pub tunables: Option<String>,
+    pub runs: Option<String>,
+}
+
+/// The archive file. `contents` is `SaveData` written out, kept as text
+/// so the checksum covers exactly what was written.
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    version: u32,
+    checksum: u64,
+    contents: String,
+}
+
+impl SaveData {
+    /// Reads everything this machine has saved.
+    pub fn gather() -> io::Result<SaveData> {
+        Ok(SaveData {
+            high_scores: read_if_there(SCORES_FILE)?,
+            tunables: read_if_there(TUNABLES_FILE)?,
+            runs: read_if_there(RUN_LOG_FILE)?,
+        })
+    }
+
+    /// Writes it all to `ARCHIVE_FILE`.
+    pub fn export(&self) -> Result<(), String> {
+        let text = pack(self)?;
+        write_replacing(Path::new(ARCHIVE_FILE), Path::new(ARCHIVE_TEMP_FILE), &text).map_err(|e| e.to_string())
+    }
+
+    /// Reads back `ARCHIVE_FILE`, if it's in one piece.
+    pub fn import() -> Result<SaveData, String> {
+        let text = fs::read_to_string(ARCHIVE_FILE).map_err(|e| format!("Can't read {}: {}", ARCHIVE_FILE, e))?;
+        unpack(&text)
+    }
+}
+
+/// What importing an archive will do, worked out before anything is
+/// written so it can be shown first.
+#[derive(Debug)]
+pub struct Import {
+    // The scores here with the archive's merged in.
+    pub high_scores: HighScores,
+    // Replaces the settings here outright.
+    tunables: Option<String>,
+    // The run history here with the archive's merged in.
+    runs: Option<String>,
+    // A line for each thing that will change.
+    pub summary: Vec<String>,
+}
+
+impl Import {
+    /// Merges `data` with what's saved here: the high score boards keep
+    /// the best runs of both, the run histories are put together and
+    /// the archive's settings replace these. Anything in the archive
+    /// that can't be read stops the whole import.
+    pub fn plan(data: &SaveData, high_scores: &HighScores, runs: Option<&str>) -> Result<Import, String> {
+        let mut summary = Vec::new();
+        let mut merged_scores = high_scores.clone();
+        if let Some(text) = &data.high_scores {
+            let theirs = HighScores::parse(text).map_err(|e| format!("Bad high scores: {}", e))?;
+            let added = merged_scores.merge(&theirs);
+            summary.push(format!("High scores: {} runs added to the leaderboard", added));
+        }
+        let tunables = match &data.tunables {
+            Some(text) => {
+                Tunables::parse(text).map_err(|e| format!("Bad settings: {}", e))?;
+                summary.push(format!("Settings: {} will be replaced", TUNABLES_FILE));
+                Some(text.clone())
+            }
+            None => None,
+        };
+        let merged_runs = match &data.runs {
+            Some(text) => {
+                let (merged, added) = runlog::merge_logs(runs, text)?;
+                summary.push(format!("Run history: {} runs added", added));
+                Some(merged)
+            }
+            None => None,
+        };
+        if summary.is_empty() {
+            summary.push("Nothing saved in the archive".to_string());
+        }
+        Ok(Import {
+            high_scores: merged_scores,
+            tunables,
+            runs: merged_runs,
+            summary,
+        })
+    }
+
+    /// Writes out the settings and run history. Each is written to
+    /// the side first and only renamed into place once all of them
+    /// are, so a failure partway leaves everything as it was. The high
+    /// scores are left to the caller to take and save.
+    pub fn apply(&self) -> io::Result<()> {
+        let files = [(TUNABLES_FILE, &self.tunables), (RUN_LOG_FILE, &self.runs)];
+        let mut written = Vec::new();
+        for (file, text) in files.iter() {
+            if let Some(text) = text {
+                let temp = format!("{}.tmp", file);
+                if let Err(e) = fs::write(&temp, text) {
+                    for (_, temp) in &written {
+                        let _ = fs::remove_file(temp);
+                    }
+                    return Err(e);
+                }
+                written.push((*file, temp));
+            }
+        }
+        for (file, temp) in &written {
+            fs::rename(temp, file)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_if_there(file: &str) -> io::Result<Option<String>> {
+    match fs::read_to_string(file) {
+        Ok(text) => Ok(Some(text)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_replacing(file: &Path, temp: &Path, text: &str) -> io::Result<()> {
+    fs::write(temp, text)?;
+    fs::rename(temp, file)
+}
+
+/// FNV-1a, enough to notice an archive that's been cut short or
+/// edited by hand.
+fn checksum(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn pack(data: &SaveData) -> Result<String, String> {
+    let contents = ron::ser::to_string(data).map_err(|e| e.to_string())?;
+    let archive = Archive {
+        version: ARCHIVE_VERSION,
+        checksum: checksum(&contents),
+        contents,
+    };
+    ron::ser::to_string(&archive).map_err(|e| e.to_string())
+}
+
+fn unpack(text: &str) -> Result<SaveData, String> {
+    let archive: Archive = ron::de::from_str(text).map_err(|e| format!("Not a save archive: {}", e))?;
+    if archive.version != ARCHIVE_VERSION {
+        return Err(format!("Archive version {} isn't supported", archive.version));
+    }
+    if checksum(&archive.contents) != archive.checksum {
+        return Err("The archive is damaged".to_string());
+    }
+    ron::de::from_str(&archive.contents).map_err(|e| format!("Not a save archive: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scores::{Difficulty, ScoreEntry};
+
+    fn entry(score: i32, started: &str) -> ScoreEntry {
+        ScoreEntry {
+            score,
+            level: 2,
+            time: 90.0,
+            started: started.to_string(),
+            mutators: Vec::new(),
+        }
+    }
+
+    fn sample() -> SaveData {
+        let scores = r#"(version: 1, boards: {
+            ("campaign", Normal): [(score: 40, level: 2, time: 90.0, started: "b", mutators: [])],
+        })"#;
+        SaveData {
+            high_scores: Some(scores.to_string()),
+            tunables: Some("(ticker: false)".to_string()),
+            runs: None,
+        }
+    }
+
+    #[test]
+    fn archives_read_back() {
+        let text = pack(&sample()).unwrap();
+        assert_eq!(unpack(&text).unwrap(), sample());
+    }
+
+    #[test]
+    fn damaged_archives_are_turned_away() {
+        let text = pack(&sample()).unwrap();
+        // Cut short.
+        assert!(unpack(&text[..text.len() / 2]).is_err());
+        // A score edited by hand.
+        let edited = text.replacen("score: 40", "score: 90", 1);
+        assert_ne!(edited, text);
+        assert_eq!(unpack(&edited).unwrap_err(), "The archive is damaged");
+        // From a newer version of the game.
+        let newer = text.replacen(&format!("version:{}", ARCHIVE_VERSION), "version:99", 1);
+        assert_ne!(newer, text);
+        assert!(unpack(&newer).unwrap_err().contains("version 99"));
+        assert!(unpack("").is_err());
+    }
+
+    #[test]
+    fn importing_merges_with_whats_here() {
+        let mut here = HighScores::default();
+        here.submit("campaign", Difficulty::Normal, entry(60, "a"));
+        here.submit("campaign", Difficulty::Normal, entry(30, "b"));
+        let import = Import::plan(&sample(), &here, None).unwrap();
+        // Run `b` got further before it was archived than it has here.
+        assert_eq!(import.high_scores.board("campaign", Difficulty::Normal), &[entry(60, "a"), entry(40, "b")][..]);
+        assert_eq!(import.tunables.as_deref(), Some("(ticker: false)"));
+        assert_eq!(import.summary.len(), 2);
+
+        let mut broken = sample();
+        broken.tunables = Some("(ticker: maybe)".to_string());
+        assert!(Import::plan(&broken, &here, None).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Commands for tuning and checking the game, only built with
+//! the `debug-tools` feature. Run them with
+//! `cargo run --features debug-tools -- <command>`.
+
+use super::*;
+
+/// Runs the command given on the command line, if there is one.
+/// Returns false when there wasn't, so the game should start.
+pub fn run_command(args: &[String]) -> bool {
+    match args.first().map(|a| a.as_str()) {
+        Some("--audit-wormholes") => {
+            let samples = args.get(1).and_then(|n| n.parse().ok()).unwrap_or(5000);
+            audit_wormholes(samples);
+            true
+        }
+        _ => false,
+    }
+}
+
+// Layout used for every level with `--grid-rocks`.
+const GRID_ROWS: i32 = 3;
+const GRID_COLS: i32 = 4;
+const GRID_SPACING: f32 = 120.0;
+
+/// Whether the game was started with `--grid-rocks`, which swaps the
+/// random rocks for a fixed grid so every run looks the same.
+pub fn grid_rocks() -> bool {
+    std::env::args().skip(1).any(|a| a == "--grid-rocks")
+}
+
+/// The rocks every level starts with under `--grid-rocks`.
+pub fn create_grid_level_rocks() -> Vec<Actor> {
+    create_rocks_grid(GRID_ROWS, GRID_COLS, GRID_SPACING)
+}
+
+/// Motionless rocks `spacing` apart, centered on the middle of the
+/// field. If `rows` and `cols` are both odd the middle rock lands
+/// right on the player.
+pub fn create_rocks_grid(rows: i32, cols: i32, spacing: f32) -> Vec<Actor> {
+    let mut rocks = Vec::with_capacity((rows * cols) as usize);
+    for row in 0..rows {
+        for col in 0..cols {
+            let mut rock = create_rock();
+            rock.pos = Point2::new(
+                (col as f32 - (cols - 1) as f32 / 2.0) * spacing,
+                (row as f32 - (rows - 1) as f32 / 2.0) * spacing,
+            );
+            rocks.push(rock);
+        }
+    }
+    rocks
+}
+
+/// What `audit_wormholes()` found.
+#[derive(Debug, Default)]
+struct WormholeAudit {
+    distances: Vec<f32>,
+    clearances: Vec<f32>,
+    // Levels where the wormhole broke each rule.
+    distance_violations: usize,
+    clearance_violations: usize,
+}
+
+/// Lays out `samples` random levels the same way the game does and
+/// prints how far the wormhole ended up from the player and the rocks,
+/// so the placement rules can be tuned.
+fn audit_wormholes(samples: usize) {
+    let min_distance = WORMHOLE_MIN_DISTANCE_FRACTION * max_wrapped_distance(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut audit = sample_wormholes(samples);
+    println!("Wormhole placement over {} levels", samples);
+    print_stats("Distance from player", &mut audit.distances);
+    print_stats("Clearance from rocks", &mut audit.clearances);
+    println!("Closer than {:.0} to the player: {}", min_distance, audit.distance_violations);
+    println!("Closer than {:.0} to a rock: {}", WORMHOLE_ROCK_CLEARANCE, audit.clearance_violations);
+}
+
+fn sample_wormholes(samples: usize) -> WormholeAudit {
+    let (sx, sy) = (SCREEN_WIDTH, SCREEN_HEIGHT);
+    let min_distance = WORMHOLE_MIN_DISTANCE_FRACTION * max_wrapped_distance(sx, sy);
+    let mut audit = WormholeAudit::default();
+
+    for i in 0..samples {
+        let level = (i % 10) as i32;
+        let player = Point2::new(
+            (rand::random::<f32>() - 0.5) * sx,
+            (rand::random::<f32>() - 0.5) * sy,
+        );
+        let rocks = create_rocks(level * 2 + 5, player, 100.0, 250.0);
+        let wormhole = place_wormhole(player, &rocks, sx, sy);
+
+        let distance = wrapped_offset(player, wormhole, sx, sy).len();
+        let clearance = wormhole_clearance(wormhole, &rocks, sx, sy);
+        if distance < min_distance {
+            audit.distance_violations += 1;
+        }
+        if clearance < WORMHOLE_ROCK_CLEARANCE {
+            audit.clearance_violations += 1;
+        }
+        audit.distances.push(distance);
+        audit.clearances.push(clearance);
+    }
+    audit
+}
+
+/// The min, median and max of `values`, sorting them on the way.
+/// With an even number the upper of the two middle values is the median.
+fn stats(values: &mut [f32]) -> Option<(f32, f32, f32)> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Some((values[0], values[values.len() / 2], values[values.len() - 1]))
+}
+
+fn print_stats(name: &str, values: &mut [f32]) {
+    if let Some((min, median, max)) = stats(values) {
+        println!("{}: min {:.1}, median {:.1}, max {:.1}", name, min, median, max);
+    }
+}
+
+// How close to the cursor a click has to be to pick an actor.
+const INSPECT_PICK_RADIUS: f32 = 20.0;
+// How far from the picked actor F3 looks for another one.
+const INSPECT_CYCLE_RADIUS: f32 = 120.0;
+
+impl Actor {
+    /// Every field worth looking at, by name, for the inspector.
+    pub fn debug_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("id", self.id.0.to_string()),
+            ("tag", format!("{:?}", self.tag)),
+            ("sys", format!("{:?}", self.sys)),
+            ("pos", format!("({:.1}, {:.1})", self.pos.x, self.pos.y)),
+            ("velocity", format!("({:.1}, {:.1})", self.velocity.x, self.velocity.y)),
+            ("facing", format!("{:.2}", self.facing)),
+            ("ang_vel", format!("{:.2}", self.ang_vel)),
+            ("life", format!("{:.2}", self.life)),
+            ("bbox_size", format!("{:.1}", self.bbox_size)),
+            ("layer", self.layer.to_string()),
+            ("armed", self.armed.to_string()),
+            ("arming", format!("{:.2}", self.arming)),
+            ("pierce_left", self.pierce_left.to_string()),
+            ("pierced", self.pierced.len().to_string()),
+            ("hit", self.hit.to_string()),
+            ("charge", format!("{:.2}", self.charge)),
+            ("size", format!("{:?}", self.size)),
+            ("last_seen", format!("{:.2}", self.last_seen)),
+        ]
+    }
+}
+
+/// The opposite of `world_to_screen_coords()`.
+fn screen_to_world_coords(screen_width: f32, screen_height: f32, point: Point2) -> Point2 {
+    Point2::new(point.x - screen_width / 2.0, screen_height / 2.0 - point.y)
+}
+
+impl MainState {
+    fn actors(&self) -> impl Iterator<Item = &Actor> {
+        std::iter::once(&self.player)
+            .chain(&self.shots)
+            .chain(&self.rocks)
+            .chain(&self.radar)
+            .chain(&self.blasts)
+            .chain(&self.wormhole)
+            .chain(&self.warps)
+            .chain(&self.pickups)
+            .chain(&self.satellites)
+    }
+
+    fn actors_mut(&mut self) -> impl Iterator<Item = &mut Actor> {
+        std::iter::once(&mut self.player)
+            .chain(&mut self.shots)
+            .chain(&mut self.rocks)
+            .chain(&mut self.radar)
+            .chain(&mut self.blasts)
+            .chain(&mut self.wormhole)
+            .chain(&mut self.warps)
+            .chain(&mut self.pickups)
+            .chain(&mut self.satellites)
+    }
+
+    /// The closest actor to `point` no more than `radius` away.
+    fn actor_near(&self, point: Point2, radius: f32) -> Option<ActorId> {
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        self.actors()
+            .map(|a| (a.id, wrapped_offset(point, a.pos, sx, sy).len()))
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(id, _)| id)
+    }
+
+    /// The actor after `id`, going round everything
+    /// close to it in the order they were made.
+    fn next_nearby(&self, id: ActorId) -> Option<ActorId> {
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        let center = self.actors().find(|a| a.id == id)?.pos;
+        let mut nearby: Vec<ActorId> = self
+            .actors()
+            .filter(|a| wrapped_offset(center, a.pos, sx, sy).len() <= INSPECT_CYCLE_RADIUS)
+            .map(|a| a.id)
+            .collect();
+        nearby.sort_by_key(|n| n.0);
+        nearby.iter().find(|n| n.0 > id.0).or_else(|| nearby.first()).cloned()
+    }
+
+    /// Clicking picks the actor under the cursor to inspect, F3
+    /// moves on to the next one near it and F2 stops it dead.
+    pub fn inspector_event(&mut self, event: &Event, window: &Window) {
+        match event {
+            Event::MouseButton(MouseButton::Left, ButtonState::Pressed) => {
+                let cursor = screen_to_world_coords(self.screen_width, self.screen_height, window.mouse().pos());
+                self.inspected = self.actor_near(cursor, INSPECT_PICK_RADIUS);
+            }
+            Event::Key(Key::F3, ButtonState::Pressed) => {
+                if let Some(id) = self.inspected {
+                    self.inspected = self.next_nearby(id);
+                }
+            }
+            Event::Key(Key::F2, ButtonState::Pressed) => {
+                if let Some(id) = self.inspected {
+                    if let Some(actor) = self.actors_mut().find(|a| a.id == id) {
+                        actor.velocity = Vector2::ZERO;
+                        actor.ang_vel = 0.0;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Rings the inspected actor and lists its fields down the left
+    /// side. Forgets it once it's gone.
+    pub fn draw_inspector(&mut self, window: &mut Window) -> quicksilver::Result<()> {
+        const SEGMENTS: i32 = 16;
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        let (pos, radius, lines) = match self.inspected.and_then(|id| self.actors().find(|a| a.id == id)) {
+            Some(actor) => {
+                let lines: Vec<String> = actor
+                    .debug_fields()
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                (actor.pos, actor.bbox_size + 6.0, lines)
+            }
+            None => {
+                self.inspected = None;
+                return Ok(());
+            }
+        };
+
+        let ring = |i: i32| {
+            let angle = 2.0 * std::f32::consts::PI * i as f32 / SEGMENTS as f32;
+            world_to_screen_coords(sx, sy, pos + vec_from_angle(angle) * radius)
+        };
+        for i in 0..SEGMENTS {
+            window.draw_ex(
+                &geom::Line::new(ring(i), ring(i + 1)),
+                Background::Col(Color::YELLOW),
+                geom::Transform::IDENTITY,
+                HUD_LAYER,
+            );
+        }
+
+        if let Some(font) = &mut self.assets.font {
+            font.execute(|f| {
+                let style = FontStyle::new(14.0, Color::YELLOW);
+                for (i, line) in lines.iter().enumerate() {
+                    let text = f.render(line, &style)?;
+                    let top_left = (10.0, 60.0 + 18.0 * i as f32);
+                    draw_hud_text(window, &geom::Rectangle::new(top_left, text.area().size()), &text);
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_samples() {
+        assert_eq!(stats(&mut []), None);
+        assert_eq!(stats(&mut [3.0]), Some((3.0, 3.0, 3.0)));
+        assert_eq!(stats(&mut [5.0, 1.0, 3.0]), Some((1.0, 3.0, 5.0)));
+        assert_eq!(stats(&mut [4.0, 1.0, 2.0, 3.0]), Some((1.0, 3.0, 4.0)));
+    }
+
+    #[test]
+    fn audit_counts_every_sample() {
+        let mut audit = sample_wormholes(200);
+        assert_eq!(audit.distances.len(), 200);
+        assert_eq!(audit.clearances.len(), 200);
+        assert_eq!((audit.distance_violations, audit.clearance_violations), (0, 0));
+        let min_distance = WORMHOLE_MIN_DISTANCE_FRACTION * max_wrapped_distance(SCREEN_WIDTH, SCREEN_HEIGHT);
+        let (min, _, max) = stats(&mut audit.distances).unwrap();
+        assert!(min >= min_distance && max <= max_wrapped_distance(SCREEN_WIDTH, SCREEN_HEIGHT));
+    }
+}
@@ -0,0 +1,139 @@
+//! The leaderboard screen, showing one board at a time picked with a
+//! row for the mode and a row for the difficulty.
+
+use crate::menu::Picker;
+use crate::scores::{Difficulty, HighScores, ScoreEntry};
+
+/// The row the left and right keys change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Mode,
+    Difficulty,
+}
+
+#[derive(Debug)]
+pub struct Leaderboard {
+    modes: Vec<String>,
+    mode: Picker,
+    difficulty: Picker,
+    filter: Filter,
+}
+
+impl Leaderboard {
+    /// Opens on the board a run in `mode` at `difficulty` goes on,
+    /// even if nothing has been put on it yet.
+    pub fn new(scores: &HighScores, mode: &str, difficulty: Difficulty) -> Leaderboard {
+        let mut modes: Vec<String> = scores.modes().into_iter().map(String::from).collect();
+        let selected = match modes.binary_search_by(|m| m.as_str().cmp(mode)) {
+            Ok(i) => i,
+            Err(i) => {
+                modes.insert(i, mode.to_string());
+                i
+            }
+        };
+        let difficulty = Difficulty::ALL.iter().position(|&d| d == difficulty).unwrap_or(0);
+        Leaderboard {
+            mode: Picker::new(modes.len(), selected),
+            modes,
+            difficulty: Picker::new(Difficulty::ALL.len(), difficulty),
+            filter: Filter::Mode,
+        }
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Moves to the other filter row.
+    pub fn switch_filter(&mut self) {
+        self.filter = match self.filter {
+            Filter::Mode => Filter::Difficulty,
+            Filter::Difficulty => Filter::Mode,
+        };
+    }
+
+    pub fn next(&mut self) {
+        match self.filter {
+            Filter::Mode => self.mode.next(),
+            Filter::Difficulty => self.difficulty.next(),
+        }
+    }
+
+    pub fn previous(&mut self) {
+        match self.filter {
+            Filter::Mode => self.mode.previous(),
+            Filter::Difficulty => self.difficulty.previous(),
+        }
+    }
+
+    pub fn mode(&self) -> &str {
+        &self.modes[self.mode.selected()]
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::ALL[self.difficulty.selected()]
+    }
+
+    /// The runs on the board the filters pick, best first.
+    pub fn entries<'a>(&self, scores: &'a HighScores) -> &'a [ScoreEntry] {
+        scores.board(self.mode(), self.difficulty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: i32) -> ScoreEntry {
+        ScoreEntry {
+            score,
+            level: 1,
+            time: 60.0,
+            started: score.to_string(),
+            mutators: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn filters_pick_the_board() {
+        let mut scores = HighScores::default();
+        scores.submit("campaign", Difficulty::Normal, entry(30));
+        scores.submit("campaign", Difficulty::Normal, entry(50));
+        scores.submit("campaign", Difficulty::Hard, entry(70));
+        scores.submit("endless", Difficulty::Normal, entry(20));
+
+        let mut board = Leaderboard::new(&scores, "campaign", Difficulty::Normal);
+        assert_eq!(board.entries(&scores), &[entry(50), entry(30)][..]);
+        board.switch_filter();
+        board.next();
+        assert_eq!(board.difficulty(), Difficulty::Hard);
+        assert_eq!(board.entries(&scores), &[entry(70)][..]);
+        board.switch_filter();
+        board.next();
+        assert_eq!(board.mode(), "endless");
+        assert!(board.entries(&scores).is_empty());
+        board.switch_filter();
+        board.previous();
+        assert_eq!(board.entries(&scores), &[entry(20)][..]);
+        // Round past the end back to the first mode.
+        board.switch_filter();
+        board.next();
+        assert_eq!(board.mode(), "campaign");
+    }
+
+    #[test]
+    fn opens_on_an_empty_board() {
+        let mut scores = HighScores::default();
+        scores.submit("campaign", Difficulty::Normal, entry(30));
+        scores.submit("practice", Difficulty::Normal, entry(10));
+        let mut board = Leaderboard::new(&scores, "endless", Difficulty::Easy);
+        assert_eq!(board.mode(), "endless");
+        assert_eq!(board.difficulty(), Difficulty::Easy);
+        assert!(board.entries(&scores).is_empty());
+        board.previous();
+        assert_eq!(board.mode(), "campaign");
+        board.next();
+        board.next();
+        assert_eq!(board.mode(), "practice");
+    }
+}
@@ -1,14 +1,66 @@
 use rand;
 
+mod archive;
+#[cfg(feature = "debug-tools")]
+mod debug_tools;
+mod leaderboard;
+mod manifest;
+mod menu;
+mod packs;
+mod runlog;
+mod scores;
+mod timestep;
+mod tunables;
+
 use quicksilver::{
     prelude::*, sound, geom,
     graphics::{self, Background, Color, Image},
     lifecycle::{Asset, Settings, State, Window, run},
 };
 
+use archive::{Import, SaveData};
+use leaderboard::{Filter, Leaderboard};
+use manifest::AssetManifest;
+use menu::{HoldToConfirm, Picker};
+use packs::AssetPack;
+use scores::{Difficulty, HighScores, ScoreEntry};
+use timestep::Timestep;
+use tunables::{FireControl, HudColors, Mutators, Tunables};
+
 type Point2 = geom::Vector;
 type Vector2 = geom::Vector;
 
+const SCREEN_WIDTH: f32 = 800.0;
+const SCREEN_HEIGHT: f32 = 600.0;
+
+const DESIRED_FPS: u32 = 60;
+// Updates run to catch up before a frame is drawn, anything past
+// this after a long stall is dropped rather than making the next
+// frame even later.
+const MAX_UPDATES_PER_FRAME: u32 = 5;
+
+// Smallest the playing field is allowed to be in either direction.
+const MIN_FIELD_SIZE: f32 = 64.0;
+
+/// Makes sure the playing field has a usable size, since
+/// wrapping doesn't make sense on a zero or negative sized field.
+fn checked_field_size(width: f32, height: f32) -> (f32, f32) {
+    let check = |name, size: f32| {
+        if size >= MIN_FIELD_SIZE {
+            size
+        } else {
+            println!("Warning: field {} of {} is too small, using {}", name, size, MIN_FIELD_SIZE);
+            MIN_FIELD_SIZE
+        }
+    };
+    (check("width", width), check("height", height))
+}
+
+/// The size of the window, and the field that fills it.
+fn field_size() -> (f32, f32) {
+    checked_field_size(SCREEN_WIDTH, SCREEN_HEIGHT)
+}
+
 /// Create a unit vector representing the
 /// given angle (in radians)
 fn vec_from_angle(angle: f32) -> Vector2 {
@@ -31,6 +83,10 @@ enum ActorType {
     Shot,
     Radar,
     Wormhole,
+    Warp,
+    Satellite,
+    Blast,
+    Pickup,
 }
 
 #[derive(Debug, PartialEq)]
@@ -40,39 +96,376 @@ enum Systems {
     Radar,
 }
 
+/// The kind of run being played. Each keeps its own high score.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum RunMode {
+    Campaign,
+    // One target at a time to practice shooting, nothing can kill you.
+    Practice,
+    // No wormhole, rocks keep coming in from the edges faster and faster.
+    Endless,
+}
+
+impl RunMode {
+    /// The key scores for this mode are saved under.
+    fn id(self) -> &'static str {
+        match self {
+            RunMode::Campaign => "campaign",
+            RunMode::Practice => "practice",
+            RunMode::Endless => "endless",
+        }
+    }
+
+    fn next(self) -> RunMode {
+        match self {
+            RunMode::Campaign => RunMode::Practice,
+            RunMode::Practice => RunMode::Endless,
+            RunMode::Endless => RunMode::Campaign,
+        }
+    }
+}
+
+// How fast practice targets drift, some don't move at all.
+const PRACTICE_TARGET_VEL: f32 = 15.0;
+// Seconds a hit or miss message stays up.
+const PRACTICE_FEEDBACK_TIME: f32 = 1.0;
+
+/// How the player is doing in target practice.
+#[derive(Debug, Default)]
+struct PracticeStats {
+    hits: u32,
+    misses: u32,
+    // Time since the current target showed up.
+    target_time: f32,
+    total_hit_time: f32,
+    // What the last shot did, shown until `feedback_time` runs out.
+    feedback: String,
+    feedback_time: f32,
+}
+
+impl PracticeStats {
+    fn average_hit_time(&self) -> f32 {
+        if self.hits == 0 {
+            0.0
+        } else {
+            self.total_hit_time / self.hits as f32
+        }
+    }
+
+    fn show_feedback(&mut self, feedback: String) {
+        self.feedback = feedback;
+        self.feedback_time = PRACTICE_FEEDBACK_TIME;
+    }
+}
+
+/// Where a level's rocks came from and what happened to them,
+/// for tuning how many rocks a level really has.
+#[derive(Debug, Default, Clone)]
+struct RockLedger {
+    spawned_initial: u32,
+    // Rocks that came in from the edges in endless mode or belt levels.
+    spawned_later: u32,
+    // Pieces that broken rocks left behind.
+    fragments: u32,
+    destroyed_by_shot: u32,
+    destroyed_by_blast: u32,
+    destroyed_by_ram: u32,
+    // The most rocks alive at once.
+    peak: usize,
+}
+
+impl RockLedger {
+    fn new(initial: usize) -> RockLedger {
+        RockLedger {
+            spawned_initial: initial as u32,
+            peak: initial,
+            ..RockLedger::default()
+        }
+    }
+
+    /// Rocks gone for any reason.
+    fn destroyed(&self) -> u32 {
+        self.destroyed_by_shot + self.destroyed_by_blast + self.destroyed_by_ram
+    }
+
+    /// Every level's ledger added up, with the peak being the busiest level's.
+    fn total<'a>(ledgers: impl Iterator<Item = &'a RockLedger>) -> RockLedger {
+        ledgers.fold(RockLedger::default(), |total, l| RockLedger {
+            spawned_initial: total.spawned_initial + l.spawned_initial,
+            spawned_later: total.spawned_later + l.spawned_later,
+            fragments: total.fragments + l.fragments,
+            destroyed_by_shot: total.destroyed_by_shot + l.destroyed_by_shot,
+            destroyed_by_blast: total.destroyed_by_blast + l.destroyed_by_blast,
+            destroyed_by_ram: total.destroyed_by_ram + l.destroyed_by_ram,
+            peak: total.peak.max(l.peak),
+        })
+    }
+}
+
+impl std::fmt::Display for RockLedger {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} spawned", self.spawned_initial)?;
+        if self.spawned_later > 0 {
+            write!(f, " (+{} later)", self.spawned_later)?;
+        }
+        if self.fragments > 0 {
+            write!(f, " (+{} pieces)", self.fragments)?;
+        }
+        write!(f, ", {} shot, {} blasted", self.destroyed_by_shot, self.destroyed_by_blast)?;
+        if self.destroyed_by_ram > 0 {
+            write!(f, ", {} rammed", self.destroyed_by_ram)?;
+        }
+        write!(f, ", at most {} at once", self.peak)
+    }
+}
+
+/// Totals for the whole run, for the run log.
+#[derive(Debug, Default, Clone, Copy)]
+struct RunStats {
+    // Seconds of play, not counting fades.
+    time: f32,
+    shots_fired: u32,
+    // Shots that hit at least one thing.
+    shots_hit: u32,
+}
+
+impl RunStats {
+    fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            0.0
+        } else {
+            self.shots_hit as f32 / self.shots_fired as f32
+        }
+    }
+
+    /// What's been added since the totals were `start`.
+    fn since(&self, start: &RunStats) -> RunStats {
+        RunStats {
+            time: self.time - start.time,
+            shots_fired: self.shots_fired - start.shots_fired,
+            shots_hit: self.shots_hit - start.shots_hit,
+        }
+    }
+}
+
+// Seconds a level should take to get through.
+const LEVEL_PAR_TIME: f32 = 20.0;
+
+// The rating each grade needs, best first. Anything lower is a C.
+const GRADE_THRESHOLDS: [(char, f32); 3] = [('S', 0.9), ('A', 0.75), ('B', 0.5)];
+// Seconds the level complete screen stays up once it's faded in.
+const LEVEL_COMPLETE_TIME: f32 = 1.5;
+
+/// A letter from S down to C for how a level went, mostly from how
+/// its `time` compared to par and the rest from `accuracy`.
+fn level_grade(time: f32, accuracy: f32) -> char {
+    let pace = if time > 0.0 { (LEVEL_PAR_TIME / time).min(1.0) } else { 1.0 };
+    grade_for_rating(0.6 * pace + 0.4 * accuracy)
+}
+
+/// The grade a 0 to 1 `rating` earns.
+fn grade_for_rating(rating: f32) -> char {
+    GRADE_THRESHOLDS
+        .iter()
+        .find(|(_, min)| rating >= *min)
+        .map_or('C', |(grade, _)| *grade)
+}
+
+/// The time limit runs start with, `None` if they don't have one.
+fn time_limit(tunables: &Tunables) -> Option<f32> {
+    if tunables.time_limit > 0.0 {
+        Some(tunables.time_limit)
+    } else {
+        None
+    }
+}
+
+/// Changes of screen that happen behind a fade to black.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ScreenChange {
+    NextLevel,
+    // A new ship after losing one, with lives to spare.
+    Respawn,
+    Restart,
+}
+
+/// Whether a run is going on or has just ended.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum GameState {
+    Playing,
+    // The last ship is gone and the world is frozen
+    // until the player asks to go again.
+    GameOver,
+}
+
+// Color a rock is tinted once it's been hit.
+const DAMAGED_ROCK_TINT: Color = Color {
+    r: 1.0,
+    g: 0.55,
+    b: 0.45,
+    a: 1.0,
+};
+
+// Seconds to fade out, and again to fade back in.
+const FADE_TIME: f32 = 0.3;
+
+// Draw layers, back to front. The world and the effects in it use
+// 485 to 510, the HUD has its own band above those so no effect can
+// cover the score, and the fade between screens covers everything.
+const HUD_LAYER: i32 = 1_000;
+const TRANSITION_LAYER: i32 = 10_000;
+
+/// What the Wepons system fires.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum WeaponMode {
+    Standard,
+    // Shots fly until fire is pressed again, then all explode.
+    Detonate,
+    // No shots, pushes rocks in front of the ship away instead.
+    Repulsor,
+    // Shots keep going through a few rocks before they die.
+    Piercing,
+}
+
+impl WeaponMode {
+    fn next(self) -> WeaponMode {
+        match self {
+            WeaponMode::Standard => WeaponMode::Detonate,
+            WeaponMode::Detonate => WeaponMode::Repulsor,
+            WeaponMode::Repulsor => WeaponMode::Piercing,
+            WeaponMode::Piercing => WeaponMode::Standard,
+        }
+    }
+}
+
+/// Tells actors apart from one update to the next, since their
+/// place in their list changes as others are removed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct ActorId(u32);
+
+impl ActorId {
+    fn next() -> ActorId {
+        static NEXT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        ActorId(NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+    }
+}
+
 #[derive(Debug)]
 struct Actor {
+    // Only the debug inspector looks at this so far.
+    #[cfg_attr(not(feature = "debug-tools"), allow(dead_code))]
+    id: ActorId,
     tag: ActorType,
     sys: Systems,
     pos: Point2,
+    // For satellites this is the angle of their orbit
+    // around the wormhole rather than where they point.
     facing: f32,
     velocity: Vector2,
     ang_vel: f32,
     bbox_size: f32,
     layer: i32,
+    // Shots that explode when the player sets them off.
+    armed: bool,
+    // Seconds before an armed shot can be set off close to the ship.
+    arming: f32,
+    // How many more rocks a shot can go through.
+    pierce_left: u32,
+    // Rocks a piercing shot has gone through, and the pieces of ones
+    // it broke, so it only hits each once on the way past.
+    pierced: Vec<ActorId>,
+    // Whether a shot has hit anything yet.
+    hit: bool,
+    // How far a wormhole has charged up, it can't be
+    // entered until this gets to 1. For radar pulses it's
+    // how far they spread compared to a normal pulse.
+    charge: f32,
+    // How big a rock is, everything else is left Large.
+    size: RockSize,
+    // When the ship last saw it, in seconds of play, for the fog.
+    last_seen: f32,
 
     // I am going to lazily overload "life" with a
     // double meaning:
-    // for shots and radar, it is the time left to live,
-    // for players and rocks, it is the actual hit points.
+    // for shots, radar and blasts, it is the time left to live,
+    // for players, rocks and satellites, it is the actual hit points.
     life: f32,
 }
 
 const PLAYER_LIFE: f32 = 1.0;
 const SHOT_LIFE: f32 = 2.0;
 const RADAR_LIFE: f32 = 3.0;
+const ARMED_SHOT_LIFE: f32 = 6.0;
+// A new armed shot can't be set off for this long while it's
+// still this close to the ship, so firing point blank and setting
+// it off straight away doesn't blow up next to the ship.
+const SHOT_ARMING_TIME: f32 = 0.1;
+const SHOT_ARMING_RADIUS: f32 = 20.0;
+const BLAST_LIFE: f32 = 0.4;
 const ROCK_LIFE: f32 = 1.0;
+// Rocks get another `ROCK_LIFE` for every this much
+// of their size, so bigger ones take more shots.
+const ROCK_LIFE_SIZE: f32 = 10.0;
+// Damage a shot does to a rock.
+const ROCK_SHOT_DAMAGE: f32 = 1.0;
+// Points for a hit that doesn't destroy a rock.
+const ROCK_HIT_SCORE: i32 = 1;
+const SATELLITE_LIFE: f32 = 2.0;
 
 const PLAYER_BBOX: f32 = 12.0;
 const ROCK_BBOX: f32 = 12.0;
 const WORMHOLE_BBOX: f32 = 16.0;
 const SHOT_BBOX: f32 = 6.0;
+const SATELLITE_BBOX: f32 = 8.0;
+// How far a blast reaches once it has fully expanded.
+const BLAST_RADIUS: f32 = 40.0;
 
 const MAX_ROCK_VEL: f32 = 50.0;
+// How much of a shot rock's velocity its pieces keep,
+// and the most speed they fly apart with on top of it.
+const FRAGMENT_VEL_KEEP: f32 = 0.6;
+const FRAGMENT_SPREAD_VEL: f32 = 40.0;
 const MAX_WORMHOLE_VEL: f32 = 25.0;
+// Seconds a new wormhole takes to charge up.
+const WORMHOLE_CHARGE_TIME: f32 = 2.0;
+// First level warps show up on.
+const WARP_MIN_LEVEL: i32 = 2;
+
+// Distance satellites keep from the wormhole.
+const SATELLITE_ORBIT_RADIUS: f32 = 60.0;
+// Orbit speed in radians per second.
+const SATELLITE_ORBIT_SPEED: f32 = 1.5;
+// First level satellites show up on.
+const SATELLITE_MIN_LEVEL: i32 = 4;
+// Damage a shot does to a satellite.
+const SATELLITE_SHOT_DAMAGE: f32 = 1.0;
+const SATELLITE_SCORE: i32 = 3;
+
+const PICKUP_BBOX: f32 = 8.0;
+// Chance each level after the first has a ram shield pickup.
+const RAM_PICKUP_CHANCE: f32 = 0.4;
+// Seconds the ram shield lasts.
+const RAM_TIME: f32 = 6.0;
+// The shield flickers for this long before it runs out.
+const RAM_WARNING_TIME: f32 = 1.5;
+const RAM_SCORE: i32 = 1;
+
+// Ships a run starts with.
+const STARTING_LIVES: i32 = 3;
+// Rocks this close to the middle are cleared away for a new ship.
+const RESPAWN_CLEAR_RADIUS: f32 = 100.0;
+// Seconds a new ship can't be hurt for.
+const INVULN_TIME: f32 = 2.0;
+// Seconds on the last ship before a spare is won back, once a level.
+const LAST_STAND_TIME: f32 = 30.0;
+// How far in from the edges the red glow on the last ship reaches.
+const LAST_STAND_EDGE: f32 = 24.0;
+// Heartbeats a second for the glow's pulse.
+const LAST_STAND_PULSE_RATE: f32 = 1.2;
 
 fn create_player() -> Actor {
     Actor {
+        id: ActorId::next(),
         tag: ActorType::Player,
         sys: Systems::Radar,
         pos: Vector2::ZERO,
@@ -81,12 +474,21 @@ fn create_player() -> Actor {
         ang_vel: 0.,
         bbox_size: PLAYER_BBOX,
         layer: 500,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
         life: PLAYER_LIFE,
     }
 }
 
 fn create_wormhole() -> Actor {
     Actor {
+        id: ActorId::next(),
         tag: ActorType::Wormhole,
         sys: Systems::Radar,
         pos: Vector2::ZERO,
@@ -95,12 +497,118 @@ fn create_wormhole() -> Actor {
         ang_vel: 0.,
         bbox_size: WORMHOLE_BBOX,
         layer: 495,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
+        life: PLAYER_LIFE,
+    }
+}
+
+/// A warp looks like a wormhole but sends the ship somewhere
+/// else on the level instead of on to the next one.
+fn create_warp() -> Actor {
+    Actor {
+        id: ActorId::next(),
+        tag: ActorType::Warp,
+        sys: Systems::Radar,
+        pos: Vector2::ZERO,
+        facing: 0.,
+        velocity: Vector2::ZERO,
+        ang_vel: 0.,
+        bbox_size: WORMHOLE_BBOX,
+        layer: 495,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
         life: PLAYER_LIFE,
     }
 }
 
+fn create_satellite() -> Actor {
+    Actor {
+        id: ActorId::next(),
+        tag: ActorType::Satellite,
+        sys: Systems::Radar,
+        pos: Vector2::ZERO,
+        facing: 0.,
+        velocity: Vector2::ZERO,
+        ang_vel: 0.,
+        bbox_size: SATELLITE_BBOX,
+        layer: 500,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
+        life: SATELLITE_LIFE,
+    }
+}
+
+/// How big a rock is. Shooting one breaks it into
+/// pieces a size down, the smallest just break up.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum RockSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl RockSize {
+    fn bbox(self) -> f32 {
+        match self {
+            RockSize::Large => ROCK_BBOX,
+            RockSize::Medium => ROCK_BBOX * 0.7,
+            RockSize::Small => ROCK_BBOX * 0.45,
+        }
+    }
+
+    /// What a rock this size breaks into.
+    fn smaller(self) -> Option<RockSize> {
+        match self {
+            RockSize::Large => Some(RockSize::Medium),
+            RockSize::Medium => Some(RockSize::Small),
+            RockSize::Small => None,
+        }
+    }
+
+    /// Points for destroying a rock this size. Smaller ones are
+    /// harder to hit, so a whole rock shot down to nothing is
+    /// worth far more than one big hit.
+    fn score(self) -> i32 {
+        match self {
+            RockSize::Large => 1,
+            RockSize::Medium => 2,
+            RockSize::Small => 3,
+        }
+    }
+}
+
+/// Hit points for a rock `bbox_size` across.
+fn rock_life(bbox_size: f32) -> f32 {
+    (bbox_size / ROCK_LIFE_SIZE).ceil().max(1.0) * ROCK_LIFE
+}
+
+/// Whether a rock has been hit but not destroyed.
+fn rock_damaged(rock: &Actor) -> bool {
+    rock.life < rock_life(rock.bbox_size)
+}
+
 fn create_rock() -> Actor {
     Actor {
+        id: ActorId::next(),
         tag: ActorType::Rock,
         sys: Systems::Radar,
         pos: Vector2::ZERO,
@@ -109,12 +617,21 @@ fn create_rock() -> Actor {
         ang_vel: 0.,
         bbox_size: ROCK_BBOX,
         layer: 500,
-        life: ROCK_LIFE,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
+        life: rock_life(ROCK_BBOX),
     }
 }
 
 fn create_shot() -> Actor {
     Actor {
+        id: ActorId::next(),
         tag: ActorType::Shot,
         sys: Systems::Radar,
         pos: Vector2::ZERO,
@@ -123,12 +640,85 @@ fn create_shot() -> Actor {
         ang_vel: SHOT_ANG_VEL,
         bbox_size: SHOT_BBOX,
         layer: 500,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
         life: SHOT_LIFE,
     }
 }
 
+fn create_blast() -> Actor {
+    Actor {
+        id: ActorId::next(),
+        tag: ActorType::Blast,
+        sys: Systems::Radar,
+        pos: Vector2::ZERO,
+        facing: 0.,
+        velocity: Vector2::ZERO,
+        ang_vel: 0.,
+        bbox_size: 0.,
+        layer: 505,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
+        life: BLAST_LIFE,
+    }
+}
+
+/// How far a radar pulse has spread. The ring drawn for it is a 16
+/// pixel circle scaled up by 10 every second.
+fn radar_radius(radar: &Actor) -> f32 {
+    16.0 * 10.0 * (RADAR_LIFE - radar.life) * radar.charge
+}
+
+/// Whether a radar pulse has spread as far as `pos`, counting
+/// the way round across the edge.
+fn radar_reaches(radar: &Actor, pos: Point2, sx: f32, sy: f32) -> bool {
+    wrapped_offset(radar.pos, pos, sx, sy).len() <= radar_radius(radar)
+}
+
+/// A ram shield pickup, it's used up when the player touches it.
+fn create_pickup() -> Actor {
+    Actor {
+        id: ActorId::next(),
+        tag: ActorType::Pickup,
+        sys: Systems::Radar,
+        pos: Vector2::ZERO,
+        facing: 0.,
+        velocity: Vector2::ZERO,
+        ang_vel: 0.,
+        bbox_size: PICKUP_BBOX,
+        layer: 500,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 0.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
+        life: PLAYER_LIFE,
+    }
+}
+
+/// Blasts grow from nothing to `BLAST_RADIUS` over their life.
+fn blast_radius(blast: &Actor) -> f32 {
+    BLAST_RADIUS * (1.0 - blast.life / BLAST_LIFE)
+}
+
 fn create_radar(layer: i32) -> Actor {
     Actor {
+        id: ActorId::next(),
         tag: ActorType::Radar,
         pos: Vector2::ZERO,
         sys: Systems::Radar,
@@ -137,6 +727,14 @@ fn create_radar(layer: i32) -> Actor {
         ang_vel: SHOT_ANG_VEL,
         bbox_size: SHOT_BBOX,
         layer: layer,
+        armed: false,
+        arming: 0.,
+        pierce_left: 0,
+        pierced: Vec::new(),
+        hit: false,
+        charge: 1.,
+        size: RockSize::Large,
+        last_seen: f32::NEG_INFINITY,
         life: RADAR_LIFE,
     }
 }
@@ -160,21 +758,361 @@ fn create_rocks(num: i32, exclusion: Point2, min_radius: f32, max_radius: f32) -
     (0..num).map(new_rock).collect()
 }
 
-fn create_wormholes(num: i32, exclusion: Point2, min_radius: f32, max_radius: f32) -> Vec<Actor> {
-    assert!(max_radius > min_radius);
+/// How many rocks a level starts with.
+fn level_rock_count(level: i32, mutators: Mutators) -> i32 {
+    let count = level * 2 + 5;
+    if mutators.double_rocks {
+        count * 2
+    } else {
+        count
+    }
+}
+
+/// The rocks a level starts with, random unless the
+/// debug tools ask for a fixed layout.
+fn create_level_rocks(num: i32, exclusion: Point2) -> Vec<Actor> {
+    #[cfg(feature = "debug-tools")]
+    {
+        if debug_tools::grid_rocks() {
+            return debug_tools::create_grid_level_rocks();
+        }
+    }
+    create_rocks(num, exclusion, 100.0, 250.0)
+}
+
+// How close to the player an edge rock is allowed to come in.
+const EDGE_SPAWN_CLEARANCE: f32 = 100.0;
+
+/// A rock coming in from a random point on the edge of the
+/// field, headed roughly towards the middle. Tries to keep
+/// clear of the player in case they're sitting by the edge.
+fn create_edge_rock(player: Point2, sx: f32, sy: f32) -> Actor {
+    let mut rock = create_rock();
+    for _ in 0..10 {
+        let along = rand::random::<f32>() - 0.5;
+        let side = if rand::random::<bool>() { 0.5 } else { -0.5 };
+        rock.pos = if rand::random::<bool>() {
+            Point2::new(side * sx, along * sy)
+        } else {
+            Point2::new(along * sx, side * sy)
+        };
+        if wrapped_offset(player, rock.pos, sx, sy).len() > EDGE_SPAWN_CLEARANCE {
+            break;
+        }
+    }
+    let inward = (-rock.pos).normalize();
+    let speed = MAX_ROCK_VEL * (0.4 + 0.6 * rand::random::<f32>());
+    rock.velocity = (inward + random_vec(0.5)).normalize() * speed;
+    rock
+}
+
+/// How a level's rocks are laid out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum LevelLayout {
+    // Scattered around the player, drifting every which way.
+    Scattered,
+    // Streaming across the field in a band between
+    // the player and the wormhole.
+    Belt,
+}
+
+// Every this many levels is a belt, starting with level 3.
+const BELT_LEVEL_INTERVAL: i32 = 4;
+// How far past the player, up or down, the middle of the belt is.
+const BELT_OFFSET: f32 = 140.0;
+// Smallest and largest the belt's half width can be.
+const BELT_MIN_HALF_WIDTH: f32 = 35.0;
+const BELT_MAX_HALF_WIDTH: f32 = 60.0;
+// Slowest and fastest the belt can flow.
+const BELT_MIN_SPEED: f32 = 25.0;
+const BELT_MAX_SPEED: f32 = 45.0;
+// Most a rock's heading can differ from the flow, in radians.
+const BELT_SPREAD: f32 = 0.2;
+// Seconds between new rocks coming in to replace lost ones.
+const BELT_DRIP_INTERVAL: f32 = 1.5;
+
+/// How the rocks of `level` are laid out.
+fn level_layout(level: i32) -> LevelLayout {
+    #[cfg(feature = "debug-tools")]
+    {
+        if debug_tools::grid_rocks() {
+            return LevelLayout::Scattered;
+        }
+    }
+    if level % BELT_LEVEL_INTERVAL == BELT_LEVEL_INTERVAL - 1 {
+        LevelLayout::Belt
+    } else {
+        LevelLayout::Scattered
+    }
+}
+
+/// A band of rocks running the width of the field, all
+/// flowing the same way.
+#[derive(Debug, Clone, Copy)]
+struct Belt {
+    center_y: f32,
+    half_width: f32,
+    // Velocity of the stream as a whole.
+    flow: Vector2,
+    // Which way from the player the belt is, 1 for up and -1 for down.
+    side: f32,
+    // How many rocks it's kept topped up to.
+    rocks: usize,
+}
+
+impl Belt {
+    /// A belt of `rocks` rocks just past the player, with
+    /// its side, width and flow picked at random.
+    fn new(player: Point2, rocks: usize, sy: f32) -> Belt {
+        let side = if rand::random::<bool>() { 1.0 } else { -1.0 };
+        let direction = if rand::random::<bool>() { 1.0 } else { -1.0 };
+        let speed = BELT_MIN_SPEED + (BELT_MAX_SPEED - BELT_MIN_SPEED) * rand::random::<f32>();
+        Belt {
+            center_y: wrap_coord(player.y + side * BELT_OFFSET, sy),
+            half_width: BELT_MIN_HALF_WIDTH + (BELT_MAX_HALF_WIDTH - BELT_MIN_HALF_WIDTH) * rand::random::<f32>(),
+            flow: Vector2::new(direction * speed, 0.0),
+            side,
+            rocks,
+        }
+    }
+
+    /// A rock in the belt at `x`, heading along with the flow.
+    fn create_rock(&self, x: f32, sy: f32) -> Actor {
+        let mut rock = create_rock();
+        let across = (rand::random::<f32>() * 2.0 - 1.0) * self.half_width;
+        rock.pos = Point2::new(x, wrap_coord(self.center_y + across, sy));
+        let spread = (rand::random::<f32>() * 2.0 - 1.0) * BELT_SPREAD;
+        let speed = self.flow.len() * (0.8 + 0.4 * rand::random::<f32>());
+        rock.velocity = vec_from_angle(self.flow.x.signum() * std::f32::consts::FRAC_PI_2 + spread) * speed;
+        rock
+    }
+
+    /// The belt's starting rocks, spread along its length.
+    fn create_rocks(&self, sx: f32, sy: f32) -> Vec<Actor> {
+        (0..self.rocks)
+            .map(|_| self.create_rock((rand::random::<f32>() - 0.5) * sx, sy))
+            .collect()
+    }
+
+    /// A wormhole on the far side of the belt, drifting along it, so
+    /// the quickest way there is across the stream.
+    fn create_wormhole(&self, sx: f32, sy: f32) -> Actor {
+        let mut wormhole = create_wormhole();
+        let gap = self.half_width + ROCK_BBOX + WORMHOLE_ROCK_CLEARANCE + WORMHOLE_BBOX;
+        wormhole.pos = Point2::new(
+            (rand::random::<f32>() - 0.5) * sx,
+            wrap_coord(self.center_y + self.side * gap, sy),
+        );
+        wormhole.velocity = Vector2::new(random_vec(MAX_WORMHOLE_VEL).x, 0.0);
+        wormhole
+    }
+}
+
+/// Brings `coord` back onto a field `size` across, centered on 0.
+fn wrap_coord(coord: f32, size: f32) -> f32 {
+    (coord + size / 2.0).rem_euclid(size) - size / 2.0
+}
+
+/// The two or three pieces `rock` breaks into, one size down, each
+/// flying off with some of its velocity. The smallest rocks
+/// don't leave anything.
+fn create_fragments(rock: &Actor) -> Vec<Actor> {
+    let size = match rock.size.smaller() {
+        Some(size) => size,
+        None => return Vec::new(),
+    };
+    let count = if rand::random::<bool>() { 3 } else { 2 };
+    let new_fragment = |_| {
+        let mut fragment = create_rock();
+        fragment.size = size;
+        fragment.bbox_size = size.bbox();
+        fragment.life = rock_life(fragment.bbox_size);
+        fragment.pos = rock.pos + random_vec(rock.bbox_size / 2.0);
+        fragment.velocity = rock.velocity * FRAGMENT_VEL_KEEP + random_vec(FRAGMENT_SPREAD_VEL);
+        fragment.last_seen = rock.last_seen;
+        fragment
+    };
+    (0..count).map(new_fragment).collect()
+}
+
+/// A single slow target for target practice, placed away from the player.
+fn create_practice_target(exclusion: Point2) -> Vec<Actor> {
+    let mut rocks = create_rocks(1, exclusion, 100.0, 250.0);
+    for rock in &mut rocks {
+        rock.velocity = random_vec(PRACTICE_TARGET_VEL);
+        rock.life = ROCK_LIFE;
+    }
+    rocks
+}
+
+/// The warps for a level, placed like wormholes. Two make a pair
+/// that lead to each other, one on its own leads anywhere.
+fn create_warps(num: i32, player: Point2, rocks: &[Actor], sx: f32, sy: f32) -> Vec<Actor> {
+    let new_warp = |_| {
+        let mut warp = create_warp();
+        warp.pos = place_wormhole(player, rocks, sx, sy);
+        warp
+    };
+    (0..num).map(new_warp).collect()
+}
+
+/// Maybe a ram shield pickup for the level, somewhere
+/// away from the player.
+fn create_pickups(player: Point2) -> Vec<Actor> {
+    if rand::random::<f32>() >= RAM_PICKUP_CHANCE {
+        return Vec::new();
+    }
+    let mut pickup = create_pickup();
+    pickup.pos = create_rocks(1, player, 150.0, 280.0)[0].pos;
+    vec![pickup]
+}
+
+/// Create the given number of wormholes, each placed by
+/// `place_wormhole()` so it's a fair distance from the player
+/// and clear of the level's rocks.
+fn create_wormholes(num: i32, player: Point2, rocks: &[Actor], sx: f32, sy: f32) -> Vec<Actor> {
     let new_wormhole = |_| {
         let mut wormhole = create_wormhole();
-        let r_angle = rand::random::<f32>() * 2.0 * std::f32::consts::PI;
-        let r_distance = rand::random::<f32>() * (max_radius - min_radius) + min_radius;
-        wormhole.pos = exclusion + vec_from_angle(r_angle) * r_distance;
+        wormhole.pos = place_wormhole(player, rocks, sx, sy);
         wormhole.velocity = random_vec(MAX_WORMHOLE_VEL);
         wormhole
     };
     (0..num).map(new_wormhole).collect()
 }
 
+/// The shortest offset from `a` to `b`, which may
+/// go across the edge of the screen and wrap around.
+fn wrapped_offset(a: Point2, b: Point2, sx: f32, sy: f32) -> Vector2 {
+    let mut offset = b - a;
+    if offset.x > sx / 2.0 {
+        offset.x -= sx;
+    } else if offset.x < -sx / 2.0 {
+        offset.x += sx;
+    }
+    if offset.y > sy / 2.0 {
+        offset.y -= sy;
+    } else if offset.y < -sy / 2.0 {
+        offset.y += sy;
+    }
+    offset
+}
+
+/// Whether the shortest path from `a` to `b`, across the wrap if
+/// that's shorter, is clear of every blocker's bounding circle.
+/// Blockers at the indices in `exclude` are skipped, so an actor
+/// doesn't block the view of itself. Just touching a blocker's
+/// edge doesn't count as blocked.
+fn line_of_sight(a: Point2, b: Point2, blockers: &[Actor], exclude: &[usize], sx: f32, sy: f32) -> bool {
+    let path = wrapped_offset(a, b, sx, sy);
+    let path_len2 = path.dot(path);
+    blockers
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !exclude.contains(i))
+        .all(|(_, blocker)| {
+            // Work relative to `a` so a blocker across the seam
+            // lines up with the path.
+            let rel = wrapped_offset(a, blocker.pos, sx, sy);
+            let t = if path_len2 > 0.0 {
+                (rel.dot(path) / path_len2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (rel - path * t).len() >= blocker.bbox_size
+        })
+}
+
+/// The farthest apart two points can be once the screen wraps,
+/// which is half the screen diagonal.
+fn max_wrapped_distance(sx: f32, sy: f32) -> f32 {
+    (sx * sx + sy * sy).sqrt() / 2.0
+}
+
+// The wormhole has to be at least this fraction of
+// `max_wrapped_distance()` away from the player. With wrapping
+// nothing is ever further than that, so 1.0 would leave a single
+// spot. 0.8 still finds a clear spot for every level in the audit.
+const WORMHOLE_MIN_DISTANCE_FRACTION: f32 = 0.8;
+// Space needed between the wormhole and the edge of any rock.
+const WORMHOLE_ROCK_CLEARANCE: f32 = 40.0;
+// Random spots to try before settling for the best one seen.
+const WORMHOLE_PLACEMENT_TRIES: i32 = 200;
+
+/// The gap between the edge of a wormhole at `spot` and the
+/// edge of the closest rock.
+fn wormhole_clearance(spot: Point2, rocks: &[Actor], sx: f32, sy: f32) -> f32 {
+    rocks
+        .iter()
+        .map(|r| wrapped_offset(spot, r.pos, sx, sy).len() - r.bbox_size - WORMHOLE_BBOX)
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// How well a wormhole at `spot` follows the placement rules,
+/// 1.0 or more means it follows all of them.
+fn wormhole_spot_score(spot: Point2, player: Point2, rocks: &[Actor], sx: f32, sy: f32) -> f32 {
+    let min_distance = WORMHOLE_MIN_DISTANCE_FRACTION * max_wrapped_distance(sx, sy);
+    let distance = wrapped_offset(player, spot, sx, sy).len();
+    let clearance = wormhole_clearance(spot, rocks, sx, sy);
+    (distance / min_distance).min(clearance / WORMHOLE_ROCK_CLEARANCE)
+}
+
+/// Picks a spot on the screen for a wormhole that isn't right on
+/// top of the player or hidden in the rocks. If no spot that follows
+/// the rules turns up in time, the best one tried is used instead.
+fn place_wormhole(player: Point2, rocks: &[Actor], sx: f32, sy: f32) -> Point2 {
+    let mut best = Point2::ZERO;
+    let mut best_score = f32::NEG_INFINITY;
+    for _ in 0..WORMHOLE_PLACEMENT_TRIES {
+        let spot = Point2::new(
+            (rand::random::<f32>() - 0.5) * sx,
+            (rand::random::<f32>() - 0.5) * sy,
+        );
+        let score = wormhole_spot_score(spot, player, rocks, sx, sy);
+        if score >= 1.0 {
+            return spot;
+        }
+        if score > best_score {
+            best = spot;
+            best_score = score;
+        }
+    }
+    best
+}
+
+/// Create the given number of satellites spaced evenly
+/// around the orbit of the wormhole at `center`.
+fn create_satellites(num: i32, center: Point2) -> Vec<Actor> {
+    let start = rand::random::<f32>() * 2.0 * std::f32::consts::PI;
+    let new_satellite = |i| {
+        let mut satellite = create_satellite();
+        satellite.facing = start + i as f32 * 2.0 * std::f32::consts::PI / num as f32;
+        update_orbit(&mut satellite, center, 0.0);
+        satellite
+    };
+    (0..num).map(new_satellite).collect()
+}
+
+/// Advances a satellite along its orbit around `center`.
+/// The position is recomputed from the orbit angle every step
+/// instead of integrating a velocity so the orbit can't drift,
+/// and it follows the wormhole exactly if that moves.
+fn update_orbit(actor: &mut Actor, center: Point2, dt: f32) {
+    actor.facing = (actor.facing + SATELLITE_ORBIT_SPEED * dt) % (2.0 * std::f32::consts::PI);
+    actor.pos = center + vec_from_angle(actor.facing) * SATELLITE_ORBIT_RADIUS;
+}
+
 const SHOT_SPEED: f32 = 200.0;
+// Shot speed with the fast shots mutator.
+const FAST_SHOT_SPEED: f32 = 320.0;
+// How far the ship can see with the fog mutator.
+const FOG_RADIUS: f32 = 120.0;
+// Seconds something takes to fade back into the fog once it's out of sight.
+const FOG_FADE_TIME: f32 = 2.0;
 const SHOT_ANG_VEL: f32 = 0.1;
+// Max random deviation of a shot from the facing, in radians either way.
+const SHOT_SPREAD: f32 = 0.0;
+// Velocity the player loses, opposite the shot, per shot fired.
+const SHOT_RECOIL: f32 = 0.0;
 
 // Acceleration in pixels per second.
 const PLAYER_THRUST: f32 = 100.0;
@@ -184,57 +1122,214 @@ const PLAYER_TURN_RATE: f32 = 3.0;
 const PLAYER_SHOT_TIME: f32 = 0.5;
 // Seconds between radar pulses
 const PLAYER_RADAR_TIME: f32 = 0.4;
+// Seconds between repulsor pulses
+const PLAYER_REPULSE_TIME: f32 = 2.0;
+
+// Energy every system draws on, it refills a bit every second.
+const MAX_ENERGY: f32 = 100.0;
+const ENERGY_REGEN: f32 = 12.0;
+// Energy a second of full thrust uses.
+const THRUST_ENERGY_COST: f32 = 15.0;
+// Energy each shot, repulse and radar pulse uses.
+const SHOT_ENERGY_COST: f32 = 6.0;
+const REPULSE_ENERGY_COST: f32 = 30.0;
+const RADAR_ENERGY_COST: f32 = 25.0;
+// How long the energy bar flashes when there isn't enough for something.
+const ENERGY_FLASH_TIME: f32 = 0.3;
+
+// Half the width of the repulsor cone, in radians.
+const REPULSOR_HALF_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+// How far the repulsor reaches.
+const REPULSOR_RANGE: f32 = 150.0;
+// Velocity given to a rock right at the ship, falling off to nothing at max range.
+const REPULSOR_IMPULSE: f32 = 120.0;
+// How long the cone stays on screen after a pulse.
+const REPULSOR_FLASH_TIME: f32 = 0.25;
 
-fn player_handle_input(actor: &mut Actor, input: &InputState, dt: f32) {
+// Seconds on one system before its bonus kicks in.
+const SYSTEM_BONUS_TIME: f32 = 10.0;
+// The bonuses, which last until the next switch.
+const ENGINES_BONUS_THRUST: f32 = 1.1;
+const WEPONS_BONUS_COOLDOWN: f32 = 0.9;
+const RADAR_BONUS_REACH: f32 = 1.15;
+
+// Real seconds the game runs slowly for after switching systems.
+const SWITCH_SLOWMO_TIME: f32 = 0.4;
+// How fast the game runs during that time.
+const SWITCH_SLOWMO_SCALE: f32 = 0.3;
+// How fast the game runs while aiming with hold to aim.
+const AIM_SLOWMO_SCALE: f32 = 0.6;
+// Length of the aim line.
+const AIM_LINE_LENGTH: f32 = 250.0;
+
+/// `thrust` is the engine's current acceleration, which depends
+/// on the thrust curve and how long the engines have been running.
+fn player_handle_input(actor: &mut Actor, input: &Actions, thrust: f32, dt: f32) {
+    // Turning works whatever system is running, on purpose: aiming
+    // the wepons and the radar's arrows depends on it, and only
+    // W is meant to be tied to a system.
     actor.facing += dt * PLAYER_TURN_RATE * input.xaxis;
 
     if input.yaxis > 0.0 {
-        player_thrust(actor, dt);
+        player_thrust(actor, thrust * input.yaxis, dt);
     }
 }
 
-fn player_thrust(actor: &mut Actor, dt: f32) {
+fn player_thrust(actor: &mut Actor, thrust: f32, dt: f32) {
     let direction_vector = vec_from_angle(actor.facing);
-    let thrust_vector = direction_vector * (PLAYER_THRUST);
+    let thrust_vector = direction_vector * (thrust);
     actor.velocity += thrust_vector * (dt);
 }
 
-const MAX_PHYSICS_VEL: f32 = 200.0;
-
-fn update_actor_position(actor: &mut Actor, dt: f32) {
-    // Clamp the velocity to the max efficiently
-    let norm_sq = actor.velocity.len2();
-    if norm_sq > MAX_PHYSICS_VEL.powi(2) {
-        actor.velocity = actor.velocity / norm_sq.sqrt() * MAX_PHYSICS_VEL;
+/// Whether `point` is within `range` of `apex` and no more than
+/// `half_angle` radians either side of the `facing` direction.
+fn point_in_cone(apex: Point2, facing: f32, half_angle: f32, range: f32, point: Point2) -> bool {
+    let offset = point - apex;
+    let distance = offset.len();
+    if distance > range {
+        return false;
     }
-    let dv = actor.velocity * (dt);
-    actor.pos += dv;
-    actor.facing += actor.ang_vel;
+    if distance == 0.0 {
+        return true;
+    }
+    offset.dot(vec_from_angle(facing)) / distance >= half_angle.cos()
 }
 
-/// Takes an actor and wraps its position to the bounds of the
-/// screen, so if it goes off the left side of the screen it
-/// will re-enter on the right side and so on.
-fn wrap_actor_position(actor: &mut Actor, sx: f32, sy: f32) {
-    // Wrap screen
-    let screen_x_bounds = sx / 2.0;
-    let screen_y_bounds = sy / 2.0;
-    if actor.pos.x > screen_x_bounds {
-        actor.pos.x -= sx;
-    } else if actor.pos.x < -screen_x_bounds {
-        actor.pos.x += sx;
-    };
-    if actor.pos.y > screen_y_bounds {
-        actor.pos.y -= sy;
-    } else if actor.pos.y < -screen_y_bounds {
-        actor.pos.y += sy;
+/// Knocks an actor by `impulse`, keeping it under `max_vel`.
+fn apply_impulse(actor: &mut Actor, impulse: Vector2, max_vel: f32) {
+    actor.velocity += impulse;
+    let speed = actor.velocity.len();
+    if speed > max_vel {
+        actor.velocity = actor.velocity / speed * max_vel;
     }
 }
 
-fn handle_timed_life(actor: &mut Actor, dt: f32) {
+// With docking on, the most the ship can be moving compared to the
+// wormhole and still go in, and how long it has to stay inside.
+const DOCKING_MAX_SPEED: f32 = 60.0;
+const DOCKING_TIME: f32 = 0.5;
+
+/// Where a ship docking with a wormhole has got to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Docking {
+    // Not touching it.
+    Apart,
+    // Slow enough, and held there this long so far.
+    Holding(f32),
+    // Held long enough to get through.
+    Docked,
+    // Coming in too fast, with the bounce to give it
+    // if it's heading in.
+    TooFast(Option<Vector2>),
+}
+
+/// Moves docking `player` with `wormhole` on by `dt`, from having
+/// been `held` in it so far.
+fn docking_step(player: &Actor, wormhole: &Actor, held: f32, dt: f32, sx: f32, sy: f32) -> Docking {
+    let offset = wrapped_offset(wormhole.pos, player.pos, sx, sy);
+    if offset.len() >= player.bbox_size + wormhole.bbox_size {
+        return Docking::Apart;
+    }
+    let relative = player.velocity - wormhole.velocity;
+    if relative.len() <= DOCKING_MAX_SPEED {
+        let held = held + dt;
+        return if held >= DOCKING_TIME { Docking::Docked } else { Docking::Holding(held) };
+    }
+    // Only bounce when heading in, so the ship isn't
+    // knocked about again on its way out.
+    let normal = if offset.len() > 0.0 {
+        offset.normalize()
+    } else {
+        -relative.normalize()
+    };
+    let closing = relative.dot(normal);
+    Docking::TooFast(if closing < 0.0 { Some(normal * (-2.0 * closing)) } else { None })
+}
+
+const MAX_PHYSICS_VEL: f32 = 200.0;
+// Speed limits for each kind of actor.
+const MAX_PLAYER_PHYSICS_VEL: f32 = 220.0;
+// Fast enough for the fast shots mutator.
+const MAX_SHOT_PHYSICS_VEL: f32 = FAST_SHOT_SPEED;
+// Well above anything rocks spawn or break up with.
+const MAX_ROCK_PHYSICS_VEL: f32 = 100.0;
+
+/// The fastest the physics lets this kind of actor go.
+fn max_physics_vel(tag: &ActorType) -> f32 {
+    match tag {
+        ActorType::Player => MAX_PLAYER_PHYSICS_VEL,
+        ActorType::Shot => MAX_SHOT_PHYSICS_VEL,
+        ActorType::Rock => MAX_ROCK_PHYSICS_VEL,
+        _ => MAX_PHYSICS_VEL,
+    }
+}
+
+fn update_actor_position(actor: &mut Actor, dt: f32) {
+    // Clamp the velocity to the max efficiently
+    let max_vel = max_physics_vel(&actor.tag);
+    let norm_sq = actor.velocity.len2();
+    if norm_sq > max_vel.powi(2) {
+        actor.velocity = actor.velocity / norm_sq.sqrt() * max_vel;
+    }
+    let dv = actor.velocity * (dt);
+    actor.pos += dv;
+    actor.facing += actor.ang_vel;
+}
+
+/// Takes an actor and wraps its position to the bounds of the
+/// screen, so if it goes off the left side of the screen it
+/// will re-enter on the right side and so on.
+/// Works even if the actor is more than a whole screen
+/// out, which can happen on a very small field. A field with
+/// no size has nowhere to wrap to, so the actor is left be.
+fn wrap_actor_position(actor: &mut Actor, sx: f32, sy: f32) {
+    if !(sx > 0.0 && sy > 0.0) {
+        return;
+    }
+    // Wrap screen
+    let screen_x_bounds = sx / 2.0;
+    let screen_y_bounds = sy / 2.0;
+    if actor.pos.x > screen_x_bounds || actor.pos.x < -screen_x_bounds {
+        actor.pos.x = (actor.pos.x + screen_x_bounds).rem_euclid(sx) - screen_x_bounds;
+    }
+    if actor.pos.y > screen_y_bounds || actor.pos.y < -screen_y_bounds {
+        actor.pos.y = (actor.pos.y + screen_y_bounds).rem_euclid(sy) - screen_y_bounds;
+    }
+}
+
+/// Keeps the actor inside the field, stopping it dead
+/// against whichever edge it runs into.
+fn clamp_actor_position(actor: &mut Actor, sx: f32, sy: f32) {
+    let screen_x_bounds = sx / 2.0;
+    let screen_y_bounds = sy / 2.0;
+    if actor.pos.x > screen_x_bounds || actor.pos.x < -screen_x_bounds {
+        actor.pos.x = actor.pos.x.clamp(-screen_x_bounds, screen_x_bounds);
+        actor.velocity.x = 0.0;
+    }
+    if actor.pos.y > screen_y_bounds || actor.pos.y < -screen_y_bounds {
+        actor.pos.y = actor.pos.y.clamp(-screen_y_bounds, screen_y_bounds);
+        actor.velocity.y = 0.0;
+    }
+}
+
+fn handle_timed_life(actor: &mut Actor, dt: f32) {
     actor.life -= dt;
 }
 
+fn handle_shot_arming(actor: &mut Actor, dt: f32) {
+    actor.arming = (actor.arming - dt).max(0.0);
+}
+
+/// Whether an armed shot can be set off yet: once it's been out for
+/// `SHOT_ARMING_TIME`, or sooner if it's already clear of the ship.
+fn shot_ready(shot: &Actor, player: Point2, sx: f32, sy: f32) -> bool {
+    shot.arming <= 0.0 || wrapped_offset(player, shot.pos, sx, sy).len() >= SHOT_ARMING_RADIUS
+}
+
+fn handle_wormhole_charge(actor: &mut Actor, dt: f32) {
+    actor.charge = (actor.charge + dt / WORMHOLE_CHARGE_TIME).min(1.0);
+}
+
 /// Translates the world coordinate system, which
 /// has Y pointing up and the origin at the center,
 /// to the screen coordinate system, which has Y
@@ -245,24 +1340,43 @@ fn world_to_screen_coords(screen_width: f32, screen_height: f32, point: Point2)
     Point2::new(x, y)
 }
 
+/// Every asset is optional: anything that can't be found in the
+/// selected pack or the defaults is drawn as a placeholder (or
+/// stays silent, for sounds) instead of crashing the game.
 struct Assets {
-    player_image: Asset<Image>,
-    shot_image: Asset<Image>,
-    rock_image: Asset<Image>,
-    font: Asset<graphics::Font>,
-    shot_sound: Asset<sound::Sound>,
-    hit_sound: Asset<sound::Sound>,
+    player_image: Option<Asset<Image>>,
+    shot_image: Option<Asset<Image>>,
+    rock_image: Option<Asset<Image>>,
+    font: Option<Asset<graphics::Font>>,
+    shot_sound: Option<Asset<sound::Sound>>,
+    hit_sound: Option<Asset<sound::Sound>>,
+    repulse_sound: Option<Asset<sound::Sound>>,
 }
 
 impl Assets {
-    fn new() -> quicksilver::Result<Assets> {
-        let player_image = Asset::new(Image::load("player.png"));
-        let shot_image = Asset::new(Image::load("shot.png"));
-        let rock_image = Asset::new(Image::load("astroid.png"));
-        let font = Asset::new(graphics::Font::load("DejaVuSerif.ttf"));
+    fn new(pack: &AssetPack, manifest: &AssetManifest) -> quicksilver::Result<Assets> {
+        // Tries the manifest's file, then the built in one if that's different.
+        let resolve = |name| {
+            let file = manifest.file(name);
+            let path = pack.resolve(file).or_else(|| match manifest::default_file(name) {
+                Some(default) if default != file => pack.resolve(default),
+                _ => None,
+            });
+            if path.is_none() {
+                println!("Using a placeholder for {}", name);
+            }
+            path
+        };
+        let image = |name| resolve(name).map(|path| Asset::new(Image::load(path)));
+        let player_image = image("player_image");
+        let shot_image = image("shot_image");
+        let rock_image = image("rock_image");
+        let font = resolve("font").map(|path| Asset::new(graphics::Font::load(path)));
 
-        let shot_sound = Asset::new(sound::Sound::load("pew.ogg"));
-        let hit_sound = Asset::new(sound::Sound::load("boom.ogg"));
+        let sound = |name| resolve(name).map(|path| Asset::new(sound::Sound::load(path)));
+        let shot_sound = sound("shot_sound");
+        let hit_sound = sound("hit_sound");
+        let repulse_sound = sound("repulse_sound");
 
         Ok(Assets {
             player_image,
@@ -271,35 +1385,242 @@ impl Assets {
             font,
             shot_sound,
             hit_sound,
+            repulse_sound,
         })
     }
 
-    fn actor_image(&mut self, actor: &Actor) -> &mut Asset<Image> {
+    fn actor_image(&mut self, actor: &Actor) -> &mut Option<Asset<Image>> {
         match actor.tag {
             ActorType::Player => &mut self.player_image,
             ActorType::Rock => &mut self.rock_image,
             ActorType::Shot => &mut self.shot_image,
             ActorType::Radar => &mut self.rock_image,
             ActorType::Wormhole => &mut self.rock_image,
+            ActorType::Warp => &mut self.rock_image,
+            ActorType::Satellite => &mut self.rock_image,
+            ActorType::Blast => &mut self.rock_image,
+            ActorType::Pickup => &mut self.rock_image,
+        }
+    }
+}
+
+/// Plays a sound if it was loaded, ignoring any playback errors.
+fn play_sound(sound: &mut Option<Asset<sound::Sound>>) {
+    if let Some(sound) = sound {
+        let _ = sound.execute(|s| s.play());
+    }
+}
+
+/// Everything in the game that makes a sound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cue {
+    Shot,
+    Hit,
+    Repulse,
+    Radar,
+}
+
+impl Cue {
+    fn sound(self, assets: &mut Assets) -> &mut Option<Asset<sound::Sound>> {
+        match self {
+            Cue::Shot => &mut assets.shot_sound,
+            Cue::Hit => &mut assets.hit_sound,
+            Cue::Repulse => &mut assets.repulse_sound,
+            Cue::Radar => &mut assets.shot_sound,
+        }
+    }
+
+    /// The caption shown in place of the sound. There's no catch
+    /// all here so a new sound can't be added without one.
+    fn caption(self) -> (&'static str, Color) {
+        match self {
+            Cue::Shot => ("[pew]", Color::YELLOW),
+            Cue::Hit => ("[boom]", Color::ORANGE),
+            Cue::Repulse => ("[whoosh]", Color::CYAN),
+            Cue::Radar => ("[ping]", Color::GREEN),
         }
     }
 }
 
+// Seconds a caption stays on screen.
+const CAPTION_TIME: f32 = 0.6;
+// The most captions shown at once, older ones are dropped.
+const MAX_CAPTIONS: usize = 4;
+
+/// Plays sounds, and shows them as captions too if visual cues are on.
+#[derive(Debug, Default)]
+struct Cues {
+    visual: bool,
+    // Captions showing and how long each has left, newest last.
+    captions: Vec<(Cue, f32)>,
+}
+
+impl Cues {
+    fn play(&mut self, assets: &mut Assets, cue: Cue) {
+        play_sound(cue.sound(assets));
+        if self.visual {
+            if self.captions.len() == MAX_CAPTIONS {
+                self.captions.remove(0);
+            }
+            self.captions.push((cue, CAPTION_TIME));
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        for (_, time) in &mut self.captions {
+            *time -= dt;
+        }
+        self.captions.retain(|(_, time)| *time > 0.0);
+    }
+}
+
+// Seconds an entry stays in the ticker.
+const TICKER_TIME: f32 = 4.0;
+// The most entries shown at once, older ones scroll off.
+const TICKER_ROWS: usize = 4;
+
+/// The last few notable things that happened, listed in a corner.
+#[derive(Debug, Default)]
+struct Ticker {
+    // Entries and how long each has left, newest last.
+    entries: Vec<(String, f32)>,
+}
+
+impl Ticker {
+    fn push(&mut self, entry: String) {
+        if self.entries.len() == TICKER_ROWS {
+            self.entries.remove(0);
+        }
+        self.entries.push((entry, TICKER_TIME));
+    }
+
+    fn update(&mut self, dt: f32) {
+        for (_, time) in &mut self.entries {
+            *time -= dt;
+        }
+        self.entries.retain(|(_, time)| *time > 0.0);
+    }
+}
+
+// What the save data menu can do, in the order it lists them.
+const SAVE_MENU_ITEMS: [&str; 2] = ["Export save data", "Import save data"];
+
+/// The save data menu, for moving everything the game saves to
+/// another machine as one archive file.
 #[derive(Debug)]
+struct SaveMenu {
+    picker: Picker,
+    // An import that's been read and checked, waiting on Enter to be
+    // held to go ahead with it.
+    import: Option<Import>,
+    confirm: HoldToConfirm,
+    // How the last export or import went.
+    message: Option<String>,
+}
+
+impl SaveMenu {
+    fn new() -> SaveMenu {
+        SaveMenu {
+            picker: Picker::new(SAVE_MENU_ITEMS.len(), 0),
+            import: None,
+            confirm: HoldToConfirm::new(),
+            message: None,
+        }
+    }
+}
+
+/// Which of the game's keys are held down. This only follows the
+/// keyboard, what the keys do is worked out each frame in `Actions`.
+#[derive(Debug, Default)]
 struct InputState {
+    // Each turn key on its own, so letting go of one still leaves
+    // the ship turning if the other key for that way is held.
+    a: bool,
+    left_arrow: bool,
+    d: bool,
+    right_arrow: bool,
+    // W, which does whatever the selected system does.
+    activate: bool,
+    // Set when a press of W was used up by a one off action
+    // (like setting off shots) and shouldn't do anything else
+    // until it's let go.
+    activate_spent: bool,
+    // How far a gamepad stick is pushed forward, 0 to 1.
+    thrust_axis: f32,
+    // The up arrow, which thrusts while firing when that's allowed.
+    thrust: bool,
+}
+
+// How far a stick has to move before it does anything.
+const STICK_DEADZONE: f32 = 0.15;
+
+impl InputState {
+    fn set_key(&mut self, key: Key, down: bool) {
+        match key {
+            Key::A => self.a = down,
+            Key::Left => self.left_arrow = down,
+            Key::D => self.d = down,
+            Key::Right => self.right_arrow = down,
+            Key::Up => self.thrust = down,
+            Key::W => {
+                self.activate = down;
+                if !down {
+                    self.activate_spent = false;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn left(&self) -> bool {
+        self.a || self.left_arrow
+    }
+
+    fn right(&self) -> bool {
+        self.d || self.right_arrow
+    }
+
+    /// Lets go of everything, for when the window loses focus and
+    /// won't hear about keys being released.
+    fn release_all(&mut self) {
+        *self = InputState::default();
+    }
+
+    /// Takes a raw stick position and keeps only the forward part,
+    /// rescaled so it starts at 0 past the deadzone.
+    fn set_thrust_axis(&mut self, value: f32) {
+        self.thrust_axis = ((value - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).clamp(0.0, 1.0);
+    }
+}
+
+/// What the player is trying to do this frame, from the held
+/// keys and the selected system. Gameplay code should only read
+/// this, never `InputState`, so switching systems mid hold just
+/// works.
+#[derive(Debug, Default)]
+struct Actions {
     xaxis: f32,
     yaxis: f32,
     fire: bool,
     radar: bool,
 }
 
-impl Default for InputState {
-    fn default() -> Self {
-        InputState {
-            xaxis: 0.0,
-            yaxis: 0.0,
-            fire: false,
-            radar: false,
+impl Actions {
+    /// `fire_and_thrust` lets the up arrow thrust while
+    /// the Wepons system is selected.
+    fn derive(input: &InputState, sys: &Systems, fire_and_thrust: bool) -> Actions {
+        let active = input.activate && !input.activate_spent;
+        Actions {
+            xaxis: input.right() as i32 as f32 - input.left() as i32 as f32,
+            // W is always full thrust, a stick gives partial thrust.
+            yaxis: match sys {
+                Systems::Engines if active => 1.0,
+                Systems::Engines => input.thrust_axis,
+                Systems::Wepons if fire_and_thrust && input.thrust => 1.0,
+                _ => 0.0,
+            },
+            fire: active && *sys == Systems::Wepons,
+            radar: active && *sys == Systems::Radar,
         }
     }
 }
@@ -308,44 +1629,239 @@ struct MainState {
     player: Actor,
     shots: Vec<Actor>,
     radar: Vec<Actor>,
+    blasts: Vec<Actor>,
     rocks: Vec<Actor>,
     wormhole: Vec<Actor>,
+    // Pairs of warps on the level, each leads to the one next to it.
+    warps: Vec<Actor>,
+    satellites: Vec<Actor>,
+    pickups: Vec<Actor>,
+    // Spots the player has marked this level.
+    waypoints: Vec<Point2>,
+    // How far each level's wormhole started from the player.
+    wormhole_distances: Vec<f32>,
+    rock_ledger: RockLedger,
+    // The ledgers of the levels already finished this run.
+    rock_ledgers: Vec<RockLedger>,
+    run_stats: RunStats,
+    // When this run started, to keep one entry for it on the leaderboard.
+    run_started: String,
+    // The run's totals when this level started.
+    level_start: RunStats,
     level: i32,
+    // Set once the wormhole is used so the level only ends once.
+    level_cleared: bool,
+    // The grade for each level got through this run.
+    level_grades: Vec<char>,
+    // How much longer the level complete screen stays up.
+    level_complete_time: f32,
+    // Points earned, before the mutators' multiplier.
     score: i32,
+    mutators: Mutators,
+    mode: RunMode,
+    practice: PracticeStats,
+    // How long this endless run has lasted.
+    endless_time: f32,
+    // Time until the next rock comes in from the edge.
+    rock_spawn_timer: f32,
+    // The current level's belt, if it's a belt level.
+    belt: Option<Belt>,
+    // Seconds until the run ends, if it has a time limit.
+    time_left: Option<f32>,
+    high_scores: HighScores,
+    // Whether scores and the run log are saved.
+    persist: bool,
     assets: Assets,
+    manifest: AssetManifest,
+    cues: Cues,
+    ticker: Ticker,
+    packs: Vec<AssetPack>,
+    pack_index: usize,
+    // Open while choosing a pack, with the world frozen behind it.
+    pack_picker: Option<Picker>,
+    // Open while looking at the high scores, also freezing the world.
+    leaderboard: Option<Leaderboard>,
+    // And while exporting or importing save data.
+    save_menu: Option<SaveMenu>,
     screen_width: f32,
     screen_height: f32,
     input: InputState,
+    actions: Actions,
+    tunables: Tunables,
+    weapon_mode: WeaponMode,
+    // Follows the current system's color, fading over on a switch.
+    hud_color: Color,
     player_shot_timeout: f32,
     player_radar_timeout: f32,
+    player_repulse_timeout: f32,
+    // How long the engines have been thrusting without a break.
+    player_thrust_time: f32,
+    switch_slowmo_timer: f32,
+    // How long the ship has been on its current system.
+    system_time: f32,
+    // Time left on the ram shield, rocks the ship touches
+    // are destroyed instead of destroying it.
+    ram_timer: f32,
+    // Ships left, counting the one flying.
+    lives: i32,
+    // Time left before a new ship can be hurt.
+    invuln_timer: f32,
+    // How long the last ship has lasted.
+    last_stand_time: f32,
+    // Set once this level's spare ship has been won back.
+    last_stand_used: bool,
+    // How long the ship has held still inside the wormhole, with docking on.
+    docking_time: f32,
+    energy: f32,
+    // Time left on the energy bar's flash after running short.
+    energy_flash: f32,
+    // Holding fire to line up a shot with hold to aim.
+    aiming: bool,
+    repulse_flash: f32,
     radar_layer: i32,
+    // How dark the screen is while changing screens, 0 to 1.
+    transition_alpha: f32,
+    // The change waiting for the screen to go black.
+    pending_change: Option<ScreenChange>,
+    state: GameState,
+    quit_hold: HoldToConfirm,
+    // Debug tools only: the world is frozen except for single
+    // steps asked for with the comma key, and the same frame is
+    // drawn over until the period key asks for the next one.
+    debug_paused: bool,
+    debug_step: bool,
+    debug_frame_step: bool,
+    // Updates run so far, to line up frame by frame captures.
+    sim_steps: u64,
+    // Frames drawn so far, held still while paused.
+    render_frames: u64,
+    // When the last frame was drawn, the updates owed since, and how
+    // many frames came so late that some of those were dropped.
+    last_draw: Option<std::time::Instant>,
+    timestep: Timestep,
+    frames_behind: u32,
+    // The actor picked out with the mouse to look inside.
+    #[cfg(feature = "debug-tools")]
+    inspected: Option<ActorId>,
 }
 
 impl MainState {
     fn new() -> quicksilver::Result<MainState> {
+        MainState::with_saves(Tunables::load(), HighScores::load(), true)
+    }
+
+    /// Starts with settings and scores that have already been loaded.
+    /// `persist` is whether scores and the run log are written back
+    /// to disk, which tests turn off.
+    fn with_saves(tunables: Tunables, high_scores: HighScores, persist: bool) -> quicksilver::Result<MainState> {
         print_instructions();
 
-        let assets = Assets::new()?;
+        let packs = packs::discover_packs();
+        let manifest = AssetManifest::load();
+        let assets = Assets::new(&packs[0], &manifest)?;
+        let mutators = tunables.mutators;
+        let (field_width, field_height) = field_size();
+        let window_size = Vector2::new(field_width, field_height);
         let player = create_player();
-        let rocks = create_rocks(5, player.pos, 100.0, 250.0);
-        let wormhole = create_wormholes(1, player.pos, 100.0, 250.0);
+        let rocks = create_level_rocks(level_rock_count(0, mutators), player.pos);
+        let wormhole = create_wormholes(1, player.pos, &rocks, window_size.x, window_size.y);
+        let rock_ledger = RockLedger::new(rocks.len());
+        let wormhole_distances = vec![wrapped_offset(player.pos, wormhole[0].pos, window_size.x, window_size.y).len()];
+
+        let mode = RunMode::Campaign;
+        println!(
+            "High score: {}",
+            high_scores.best(mode.id(), Difficulty::from_multiplier(mutators.score_multiplier()))
+        );
+        if !mutators.names().is_empty() {
+            println!("Mutators: {} (score x{:.2})", mutators.names().join(", "), mutators.score_multiplier());
+        }
+        println!();
+
+        let cues = Cues {
+            visual: tunables.visual_cues,
+            ..Cues::default()
+        };
 
-        let window_size = Vector2::new(800.0, 600.0);
+        let hud_color = system_color(&player.sys, &tunables.hud_colors);
         let s = MainState {
             player,
             shots: Vec::new(),
             radar: Vec::new(),
+            blasts: Vec::new(),
             rocks,
             wormhole,
+            warps: Vec::new(),
+            satellites: Vec::new(),
+            pickups: Vec::new(),
+            waypoints: Vec::new(),
+            wormhole_distances,
+            rock_ledger,
+            rock_ledgers: Vec::new(),
+            run_stats: RunStats::default(),
+            run_started: runlog::utc_now(),
+            level_start: RunStats::default(),
             level: 0,
+            level_cleared: false,
+            level_grades: Vec::new(),
+            level_complete_time: 0.0,
             score: 0,
+            mutators,
+            mode,
+            practice: PracticeStats::default(),
+            endless_time: 0.0,
+            rock_spawn_timer: 0.0,
+            belt: None,
+            time_left: time_limit(&tunables),
+            high_scores,
+            persist,
             assets,
+            manifest,
+            cues,
+            ticker: Ticker::default(),
+            packs,
+            pack_index: 0,
+            pack_picker: None,
+            leaderboard: None,
+            save_menu: None,
             screen_width: window_size.x,
             screen_height: window_size.y,
             input: InputState::default(),
+            actions: Actions::default(),
+            hud_color,
+            tunables,
+            weapon_mode: WeaponMode::Standard,
             player_shot_timeout: 0.0,
             player_radar_timeout: 0.0,
+            player_repulse_timeout: 0.0,
+            player_thrust_time: 0.0,
+            switch_slowmo_timer: 0.0,
+            system_time: 0.0,
+            ram_timer: 0.0,
+            lives: STARTING_LIVES,
+            invuln_timer: 0.0,
+            last_stand_time: 0.0,
+            last_stand_used: false,
+            docking_time: 0.0,
+            energy: MAX_ENERGY,
+            energy_flash: 0.0,
+            aiming: false,
+            repulse_flash: 0.0,
             radar_layer: 0,
+            transition_alpha: 0.0,
+            pending_change: None,
+            state: GameState::Playing,
+            quit_hold: HoldToConfirm::new(),
+            debug_paused: false,
+            debug_step: false,
+            debug_frame_step: false,
+            sim_steps: 0,
+            render_frames: 0,
+            last_draw: None,
+            timestep: Timestep::new(1.0 / DESIRED_FPS as f32, MAX_UPDATES_PER_FRAME),
+            frames_behind: 0,
+            #[cfg(feature = "debug-tools")]
+            inspected: None,
         };
 
         Ok(s)
@@ -355,30 +1871,252 @@ impl MainState {
         self.player = create_player();
         self.shots = Vec::new();
         self.radar = Vec::new();
-        self.rocks = create_rocks(5, self.player.pos, 100.0, 250.0);
-        self.wormhole = create_wormholes(1, self.player.pos, 100.0, 250.0);
+        self.blasts = Vec::new();
+        if self.mode == RunMode::Practice {
+            self.rocks = create_practice_target(self.player.pos);
+            self.wormhole = Vec::new();
+            self.wormhole_distances = Vec::new();
+        } else if self.mode == RunMode::Endless {
+            self.rocks = Vec::new();
+            self.wormhole = Vec::new();
+            self.wormhole_distances = Vec::new();
+        } else {
+            self.rocks = create_level_rocks(level_rock_count(0, self.mutators), self.player.pos);
+            self.wormhole = create_wormholes(1, self.player.pos, &self.rocks, self.screen_width, self.screen_height);
+            self.wormhole_distances = vec![self.wormhole_distance()];
+        }
+        self.practice = PracticeStats::default();
+        self.endless_time = 0.0;
+        self.rock_spawn_timer = 0.0;
+        self.belt = None;
+        self.time_left = time_limit(&self.tunables);
+        self.rock_ledger = RockLedger::new(self.rocks.len());
+        self.rock_ledgers = Vec::new();
+        self.run_stats = RunStats::default();
+        self.run_started = runlog::utc_now();
+        self.level_start = RunStats::default();
+        self.warps = Vec::new();
+        self.satellites = Vec::new();
+        self.pickups = Vec::new();
+        self.ram_timer = 0.0;
+        self.lives = STARTING_LIVES;
+        self.invuln_timer = 0.0;
+        self.last_stand_time = 0.0;
+        self.last_stand_used = false;
+        self.docking_time = 0.0;
+        self.state = GameState::Playing;
+        self.energy = MAX_ENERGY;
+        self.energy_flash = 0.0;
+        self.waypoints = Vec::new();
         self.level = 0;
+        self.level_cleared = false;
+        self.level_grades = Vec::new();
         self.score = 0;
-        self.input = InputState::default();
+        self.actions = Actions::default();
+        self.weapon_mode = WeaponMode::Standard;
         self.player_shot_timeout = 0.0;
         self.player_radar_timeout = 0.0;
+        self.player_repulse_timeout = 0.0;
+        self.player_thrust_time = 0.0;
+        self.switch_slowmo_timer = 0.0;
+        self.system_time = 0.0;
+        self.aiming = false;
+        self.ticker = Ticker::default();
+        self.repulse_flash = 0.0;
         self.radar_layer = 0;
     }
 
-    fn fire_player_shot(&mut self) {
-        self.player_shot_timeout = PLAYER_SHOT_TIME;
+    fn switch_system(&mut self, sys: Systems) {
+        if self.player.sys != sys {
+            if self.tunables.switch_slowmo {
+                self.switch_slowmo_timer = SWITCH_SLOWMO_TIME;
+            }
+            self.system_time = 0.0;
+        }
+        self.player.sys = sys;
+    }
+
+    /// Whether the ship has stayed on its system long enough for the bonus.
+    fn system_bonus(&self) -> bool {
+        self.system_time >= SYSTEM_BONUS_TIME
+    }
 
-        let player = &self.player;
+    /// Opens the leaderboard on the board this run would go on.
+    fn open_leaderboard(&mut self) {
+        self.leaderboard = Some(Leaderboard::new(&self.high_scores, &self.score_key(), self.difficulty()));
+    }
+
+    /// Does whatever is picked in the save data menu. An import is only
+    /// read and checked here, it waits for `finish_import`.
+    fn save_menu_select(&mut self) {
+        let menu = match &mut self.save_menu {
+            Some(menu) => menu,
+            None => return,
+        };
+        menu.message = Some(match menu.picker.selected() {
+            0 => match SaveData::gather().map_err(|e| e.to_string()).and_then(|data| data.export()) {
+                Ok(()) => format!("Saved to {}", archive::ARCHIVE_FILE),
+                Err(e) => format!("Export failed: {}", e),
+            },
+            _ => {
+                let runs = std::fs::read_to_string(runlog::RUN_LOG_FILE).ok();
+                let high_scores = &self.high_scores;
+                match SaveData::import().and_then(|data| Import::plan(&data, high_scores, runs.as_deref())) {
+                    Ok(import) => {
+                        menu.import = Some(import);
+                        "Hold Enter to import, Esc to cancel".to_string()
+                    }
+                    Err(e) => format!("Import failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Writes out the import waiting in the save data menu.
+    fn finish_import(&mut self) {
+        let import = match self.save_menu.as_mut().and_then(|menu| menu.import.take()) {
+            Some(import) => import,
+            None => return,
+        };
+        let message = match import.apply() {
+            Ok(()) => {
+                self.high_scores = import.high_scores;
+                self.save_scores();
+                "Imported, new settings are used from the next start".to_string()
+            }
+            Err(e) => format!("Import failed: {}", e),
+        };
+        println!("{}", message);
+        if let Some(menu) = &mut self.save_menu {
+            menu.message = Some(message);
+        }
+    }
+
+    /// Switches to asset pack `index` and reloads everything from it.
+    fn select_asset_pack(&mut self, index: usize) -> quicksilver::Result<()> {
+        self.pack_index = index;
+        let pack = &self.packs[self.pack_index];
+        match &pack.author {
+            Some(author) => println!("Asset pack: {} by {}", pack.name, author),
+            None => println!("Asset pack: {}", pack.name),
+        }
+        self.assets = Assets::new(pack, &self.manifest)?;
+        Ok(())
+    }
+
+    /// Uses up `cost` energy if there's that much left, otherwise
+    /// flashes the energy bar and returns false.
+    fn spend_energy(&mut self, cost: f32) -> bool {
+        if self.energy < cost {
+            self.energy_flash = ENERGY_FLASH_TIME;
+            return false;
+        }
+        self.energy -= cost;
+        true
+    }
+
+    /// Aimed shots go exactly where the ship is pointing.
+    fn fire_player_shot(&mut self, aimed: bool) {
+        self.player_shot_timeout = if self.system_bonus() {
+            PLAYER_SHOT_TIME * WEPONS_BONUS_COOLDOWN
+        } else {
+            PLAYER_SHOT_TIME
+        };
+
+        let player = &mut self.player;
         let mut shot = create_shot();
         shot.pos = player.pos;
         shot.facing = player.facing;
+        if !aimed {
+            shot.facing += (rand::random::<f32>() * 2.0 - 1.0) * SHOT_SPREAD;
+        }
         let direction = vec_from_angle(shot.facing);
-        shot.velocity.x = SHOT_SPEED * direction.x;
-        shot.velocity.y = SHOT_SPEED * direction.y;
+        let speed = if self.mutators.fast_shots { FAST_SHOT_SPEED } else { SHOT_SPEED };
+        shot.velocity.x = speed * direction.x;
+        shot.velocity.y = speed * direction.y;
+        player.velocity -= direction * SHOT_RECOIL;
+        match self.weapon_mode {
+            WeaponMode::Detonate => {
+                shot.armed = true;
+                shot.arming = SHOT_ARMING_TIME;
+                shot.life = ARMED_SHOT_LIFE;
+            }
+            WeaponMode::Piercing => shot.pierce_left = self.tunables.shot_pierce,
+            _ => (),
+        }
 
         self.shots.push(shot);
+        self.run_stats.shots_fired += 1;
+
+        self.cues.play(&mut self.assets, Cue::Shot);
+    }
+
+    /// Picks up the closest waypoint if the ship is on one,
+    /// otherwise drops a new one where the ship is.
+    fn toggle_waypoint(&mut self) {
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        let player = self.player.pos;
+        let nearby = self
+            .waypoints
+            .iter()
+            .map(|w| wrapped_offset(player, *w, sx, sy).len())
+            .enumerate()
+            .filter(|(_, d)| *d <= WAYPOINT_REMOVE_RADIUS)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+        match nearby {
+            Some((i, _)) => {
+                self.waypoints.remove(i);
+            }
+            None => {
+                if self.waypoints.len() == MAX_WAYPOINTS {
+                    self.waypoints.remove(0);
+                }
+                self.waypoints.push(player);
+            }
+        }
+    }
+
+    /// Blows up every armed shot in flight, except ones fired so
+    /// recently they're still right next to the ship.
+    fn detonate_shots(&mut self) {
+        let (player, sx, sy) = (self.player.pos, self.screen_width, self.screen_height);
+        let mut detonated = false;
+        for shot in self.shots.iter_mut().filter(|s| s.armed && shot_ready(s, player, sx, sy)) {
+            shot.life = 0.0;
+            let mut blast = create_blast();
+            blast.pos = shot.pos;
+            self.blasts.push(blast);
+            detonated = true;
+        }
+
+        if detonated {
+            self.cues.play(&mut self.assets, Cue::Hit);
+        }
+    }
+
+    /// Pushes every rock in the cone in front of the ship away,
+    /// harder the closer it is. Does no damage.
+    fn fire_player_repulsor(&mut self) {
+        self.player_repulse_timeout = PLAYER_REPULSE_TIME;
+        self.repulse_flash = REPULSOR_FLASH_TIME;
+
+        let player = &self.player;
+        for rock in &mut self.rocks {
+            if !point_in_cone(player.pos, player.facing, REPULSOR_HALF_ANGLE, REPULSOR_RANGE, rock.pos) {
+                continue;
+            }
+            let offset = rock.pos - player.pos;
+            let distance = offset.len();
+            let direction = if distance > 0.0 {
+                offset / distance
+            } else {
+                vec_from_angle(player.facing)
+            };
+            let strength = REPULSOR_IMPULSE * (1.0 - distance / REPULSOR_RANGE);
+            apply_impulse(rock, direction * strength, MAX_ROCK_VEL);
+        }
 
-        let _ = self.assets.shot_sound.execute(|s| s.play());
+        self.cues.play(&mut self.assets, Cue::Repulse);
     }
 
     fn fire_player_radar(&mut self) {
@@ -387,46 +2125,229 @@ impl MainState {
         let player = &self.player;
         let mut radar = create_radar(self.radar_layer);
         radar.pos = player.pos;
+        if self.system_bonus() {
+            radar.charge = RADAR_BONUS_REACH;
+        }
         self.radar_layer = self.radar_layer + 2;
 
         self.radar.push(radar);
 
-        let _ = self.assets.shot_sound.execute(|s| s.play());
+        self.cues.play(&mut self.assets, Cue::Radar);
     }
 
     fn clear_dead_stuff(&mut self) {
         self.shots.retain(|s| s.life > 0.0);
         self.rocks.retain(|r| r.life > 0.0);
         self.radar.retain(|r| r.life > 0.0);
+        self.blasts.retain(|b| b.life > 0.0);
         self.wormhole.retain(|w| w.life > 0.0);
+        self.satellites.retain(|s| s.life > 0.0);
+        self.pickups.retain(|p| p.life > 0.0);
         if self.radar.len() == 0 {
             self.radar_layer = 0
         }
     }
 
     fn handle_collisions(&mut self) {
+        // Added once the rocks are done with, and
+        // not checked for hits until the next step.
+        let mut fragments = Vec::new();
+        // Practice targets go all at once so a hit is a hit.
+        let rocks_break = self.mode != RunMode::Practice;
         for rock in &mut self.rocks {
+            // Already destroyed in an earlier substep.
+            if rock.life <= 0.0 {
+                continue;
+            }
             let pdistance = rock.pos - self.player.pos;
             if pdistance.len() < (self.player.bbox_size + rock.bbox_size) {
-                self.player.life = 0.0;
+                // Rammed rocks are smashed to nothing, pieces left on
+                // top of the ship would kill it when the shield ran out.
+                if self.ram_timer > 0.0 {
+                    rock.life = 0.0;
+                    self.score += RAM_SCORE * rock.size.score();
+                    self.rock_ledger.destroyed_by_ram += 1;
+                    self.cues.play(&mut self.assets, Cue::Hit);
+                    continue;
+                }
+                if self.mode != RunMode::Practice && self.invuln_timer <= 0.0 {
+                    self.player.life = 0.0;
+                }
             }
             for shot in &mut self.shots {
+                if shot.life <= 0.0 || rock.life <= 0.0 || shot.pierced.contains(&rock.id) {
+                    continue;
+                }
                 let distance = shot.pos - rock.pos;
                 if distance.len() < (shot.bbox_size + rock.bbox_size) {
-                    shot.life = 0.0;
+                    if !shot.hit {
+                        shot.hit = true;
+                        self.run_stats.shots_hit += 1;
+                    }
+                    rock.life -= ROCK_SHOT_DAMAGE;
+                    // Piercing shots carry on through any rock they hit,
+                    // broken or not.
+                    if shot.pierce_left > 0 {
+                        shot.pierce_left -= 1;
+                        shot.pierced.push(rock.id);
+                    } else {
+                        shot.life = 0.0;
+                    }
+                    if rock.life > 0.0 {
+                        self.score += ROCK_HIT_SCORE;
+                    } else {
+                        self.score += rock.size.score();
+                        self.rock_ledger.destroyed_by_shot += 1;
+                        if rocks_break {
+                            let pieces = create_fragments(rock);
+                            if shot.life > 0.0 {
+                                shot.pierced.extend(pieces.iter().map(|piece| piece.id));
+                            }
+                            fragments.extend(pieces);
+                        }
+                    }
+
+                    self.cues.play(&mut self.assets, Cue::Hit);
+                }
+            }
+            for blast in &self.blasts {
+                if rock.life <= 0.0 {
+                    break;
+                }
+                let distance = blast.pos - rock.pos;
+                if distance.len() < (blast_radius(blast) + rock.bbox_size) {
                     rock.life = 0.0;
-                    self.score += 1;
+                    self.score += rock.size.score();
+                    self.rock_ledger.destroyed_by_blast += 1;
+                    if rocks_break {
+                        fragments.extend(create_fragments(rock));
+                    }
+                }
+            }
+        }
+        self.rock_ledger.fragments += fragments.len() as u32;
+        self.rocks.extend(fragments);
+        for satellite in &mut self.satellites {
+            let pdistance = satellite.pos - self.player.pos;
+            if pdistance.len() < (self.player.bbox_size + satellite.bbox_size) && self.invuln_timer <= 0.0 {
+                self.player.life = 0.0;
+            }
+            for shot in &mut self.shots {
+                if shot.life <= 0.0 || satellite.life <= 0.0 {
+                    continue;
+                }
+                let distance = shot.pos - satellite.pos;
+                if distance.len() < (shot.bbox_size + satellite.bbox_size) {
+                    if !shot.hit {
+                        shot.hit = true;
+                        self.run_stats.shots_hit += 1;
+                    }
+                    shot.life = 0.0;
+                    satellite.life -= SATELLITE_SHOT_DAMAGE;
+                    if satellite.life <= 0.0 {
+                        self.score += SATELLITE_SCORE;
+                        if self.tunables.ticker {
+                            self.ticker.push(format!("Satellite destroyed +{}", SATELLITE_SCORE));
+                        }
+                    }
 
-                    let _ = self.assets.hit_sound.execute(|s| s.play());
+                    self.cues.play(&mut self.assets, Cue::Hit);
+                }
+            }
+        }
+        for pickup in &mut self.pickups {
+            let pdistance = pickup.pos - self.player.pos;
+            if pdistance.len() < (self.player.bbox_size + pickup.bbox_size) {
+                pickup.life = 0.0;
+                self.ram_timer = RAM_TIME;
+                if self.tunables.ticker {
+                    self.ticker.push("Ram shield".to_string());
                 }
             }
         }
         for wormhole in &mut self.wormhole {
-            let pdistance = wormhole.pos - self.player.pos;
+            // Wormholes do nothing until they've charged up,
+            // and docking is handled on its own.
+            if wormhole.charge < 1.0 || self.tunables.docking {
+                continue;
+            }
+            // Across the edge counts, the wormhole can be wrapping
+            // around as the ship reaches it.
+            let pdistance = wrapped_offset(self.player.pos, wormhole.pos, self.screen_width, self.screen_height);
             if pdistance.len() < (self.player.bbox_size + wormhole.bbox_size) {
                 wormhole.life = 0.;
             }
         }
+        self.handle_warps();
+    }
+
+    /// Sends the ship out of the other end of a charged warp it
+    /// touches, or somewhere clear of rocks if it has no pair, still
+    /// going the same way. Both ends have to charge up again before
+    /// they can be used, so the ship can't bounce straight back.
+    fn handle_warps(&mut self) {
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        let player = &self.player;
+        let entered = self
+            .warps
+            .iter()
+            .position(|w| w.charge >= 1.0 && wrapped_offset(player.pos, w.pos, sx, sy).len() < player.bbox_size + w.bbox_size);
+        let entered = match entered {
+            Some(i) => i,
+            None => return,
+        };
+        // Warps are paired off in order, 0 with 1 and so on.
+        let paired = entered ^ 1;
+        let exit = match self.warps.get(paired) {
+            Some(warp) => warp.pos,
+            None => place_wormhole(self.player.pos, &self.rocks, sx, sy),
+        };
+        let heading = if self.player.velocity.len() > 0.0 {
+            self.player.velocity.normalize()
+        } else {
+            vec_from_angle(self.player.facing)
+        };
+        // Just outside the exit, so the ship isn't sitting on it.
+        self.player.pos = exit + heading * (self.player.bbox_size + WORMHOLE_BBOX);
+        if self.mutators.no_wrap {
+            clamp_actor_position(&mut self.player, sx, sy);
+        } else {
+            wrap_actor_position(&mut self.player, sx, sy);
+        }
+        for i in &[entered, paired] {
+            if let Some(warp) = self.warps.get_mut(*i) {
+                warp.charge = 0.0;
+            }
+        }
+        self.cues.play(&mut self.assets, Cue::Repulse);
+    }
+
+    /// With docking on the ship has to ease into the wormhole and
+    /// sit in it for `DOCKING_TIME` to get through. Coming in faster
+    /// than `DOCKING_MAX_SPEED` bounces it off with a scrape, and
+    /// leaving or speeding up starts the docking over.
+    fn update_docking(&mut self, dt: f32) {
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        for wormhole in &mut self.wormhole {
+            if wormhole.charge < 1.0 {
+                continue;
+            }
+            match docking_step(&self.player, wormhole, self.docking_time, dt, sx, sy) {
+                Docking::Apart => self.docking_time = 0.0,
+                Docking::Holding(held) => self.docking_time = held,
+                Docking::Docked => {
+                    self.docking_time = 0.0;
+                    wormhole.life = 0.;
+                }
+                Docking::TooFast(bounce) => {
+                    self.docking_time = 0.0;
+                    if let Some(impulse) = bounce {
+                        apply_impulse(&mut self.player, impulse, MAX_PLAYER_PHYSICS_VEL);
+                        self.cues.play(&mut self.assets, Cue::Hit);
+                    }
+                }
+            }
+        }
     }
 
     // fn check_for_level_respawn(&mut self) {
@@ -438,262 +2359,2585 @@ impl MainState {
     // }
 
     fn check_for_level_end(&mut self) {
-        if self.wormhole.is_empty() {
-            self.score += 10;
-            self.level += 1;
-            self.wormhole = create_wormholes(1, self.player.pos, 100.0, 250.0);
-            self.rocks = create_rocks(self.level * 2 + 5, self.player.pos, 100.0, 250.0);
+        if self.wormhole.is_empty() && !self.level_cleared {
+            self.level_cleared = true;
+            let (level_stats, accuracy) = self.level_stats();
+            self.level_grades.push(level_grade(level_stats.time, accuracy));
+            self.level_complete_time = LEVEL_COMPLETE_TIME;
+            self.start_transition(ScreenChange::NextLevel);
         }
     }
-}
 
-/// **********************************************************************
-/// A couple of utility functions.
-/// **********************************************************************
+    /// Scores target practice: shots that run out of time are
+    /// misses, and a target being gone is a hit.
+    fn update_practice(&mut self, dt: f32, missed_shots: u32) {
+        let practice = &mut self.practice;
+        practice.target_time += dt;
+        practice.feedback_time -= dt;
+        if missed_shots > 0 {
+            practice.misses += missed_shots;
+            practice.show_feedback("Miss".to_string());
+        }
+        if self.rocks.is_empty() {
+            practice.hits += 1;
+            practice.total_hit_time += practice.target_time;
+            practice.show_feedback(format!("Hit! {:.2}s", practice.target_time));
+            practice.target_time = 0.0;
+            self.rocks = create_practice_target(self.player.pos);
+        }
+    }
 
-fn print_instructions() {
-    println!();
-    println!("Welcome to Systems Critical");
-    println!();
-    println!("How to play:");
-    println!("Switch ship systems with 1,2,3");
-    println!("1 engines: you can move forward with w");
-    println!("2 wepons: fire wepons with w");
-    println!("3 rader: scan the surronding area with w");
-    println!();
-}
+    /// Brings in rocks from the edges, more often the longer
+    /// the run goes on, up to the most allowed at once.
+    fn update_endless(&mut self, dt: f32) {
+        let ramp = &self.tunables.endless;
+        self.endless_time += dt;
+        self.rock_spawn_timer -= dt;
+        if self.rock_spawn_timer <= 0.0 {
+            if self.rocks.len() < ramp.max_rocks {
+                self.rocks.push(create_edge_rock(self.player.pos, self.screen_width, self.screen_height));
+                self.rock_ledger.spawned_later += 1;
+            }
+            self.rock_spawn_timer = ramp.spawn_interval(self.endless_time);
+        }
+    }
 
-fn draw_actor(
-    assets: &mut Assets,
-    window: &mut Window,
-    actor: &Actor,
-    world_coords: (f32, f32),
-) -> quicksilver::Result<()> {
-    let (screen_w, screen_h) = world_coords;
-    let pos = world_to_screen_coords(screen_w, screen_h, actor.pos);
-    let image = assets.actor_image(actor);
-    if actor.tag == ActorType::Radar {
-        let scale = ((RADAR_LIFE - actor.life).trunc() + (RADAR_LIFE - actor.life + 1.).fract()) * 10.;
-        let transform = geom::Transform::scale((scale, scale));
-        window.draw_ex(
-            &geom::Circle::new((pos.x, pos.y), 16),
-            Background::Col(Color::GREEN),
-            transform,
-            actor.layer,
-        );
-        window.draw_ex(
-            &geom::Circle::new((pos.x, pos.y), 15),
-            Background::Col(Color::BLACK),
-            transform,
-            actor.layer + 1,
-        );
-        Ok(())
-    } else if actor.tag == ActorType::Wormhole {
-        window.draw_ex(
-            &geom::Circle::new((pos.x, pos.y), 14),
-            Background::Col(Color::PURPLE),
-            geom::Transform::IDENTITY,
-            actor.layer,
-        );
+    /// Keeps a belt level's stream going by bringing in a rock at
+    /// the upstream edge every so often while it's short of rocks.
+    fn update_belt(&mut self, dt: f32) {
+        let belt = match &self.belt {
+            Some(belt) => *belt,
+            None => return,
+        };
+        self.rock_spawn_timer -= dt;
+        if self.rock_spawn_timer > 0.0 || self.rocks.len() >= belt.rocks {
+            return;
+        }
+        let x = -belt.flow.x.signum() * self.screen_width / 2.0;
+        let rock = belt.create_rock(x, self.screen_height);
+        // Wait for the player to move out of the way rather than
+        // dropping a rock on top of them.
+        if wrapped_offset(self.player.pos, rock.pos, self.screen_width, self.screen_height).len() > EDGE_SPAWN_CLEARANCE {
+            self.rocks.push(rock);
+            self.rock_ledger.spawned_later += 1;
+            self.rock_spawn_timer = BELT_DRIP_INTERVAL;
+        }
+    }
+
+    /// How the level so far has gone, with its accuracy.
+    fn level_stats(&self) -> (RunStats, f32) {
+        let level_stats = self.run_stats.since(&self.level_start);
+        // Getting through without firing doesn't count as missing.
+        let accuracy = if level_stats.shots_fired == 0 { 1.0 } else { level_stats.accuracy() };
+        (level_stats, accuracy)
+    }
+
+    fn next_level(&mut self) {
+        let ledger = std::mem::take(&mut self.rock_ledger);
+        if cfg!(feature = "debug-tools") {
+            println!("Level {} rocks: {}", self.level, ledger);
+        }
+        self.rock_ledgers.push(ledger);
+
+        let (level_stats, _) = self.level_stats();
+        self.level_start = self.run_stats;
+
+        self.score += 10;
+        // Saved as it goes so a crash doesn't lose the run's best.
+        if self.submit_score().is_some() {
+            self.save_scores();
+        }
+        if self.tunables.ticker {
+            self.ticker.push("Wormhole reached +10".to_string());
+            self.ticker.push(format!(
+                "Level {} in {:.0}s, {}/{} hits: {}",
+                self.level,
+                level_stats.time,
+                level_stats.shots_hit,
+                level_stats.shots_fired,
+                self.level_grades.last().copied().unwrap_or('C')
+            ));
+        }
+        self.level += 1;
+        self.level_cleared = false;
+        self.last_stand_used = false;
+        let rock_count = level_rock_count(self.level, self.mutators);
+        self.belt = match level_layout(self.level) {
+            LevelLayout::Belt => Some(Belt::new(self.player.pos, rock_count as usize, self.screen_height)),
+            LevelLayout::Scattered => None,
+        };
+        if let Some(belt) = &self.belt {
+            self.rocks = belt.create_rocks(self.screen_width, self.screen_height);
+            self.wormhole = vec![belt.create_wormhole(self.screen_width, self.screen_height)];
+        } else {
+            self.rocks = create_level_rocks(rock_count, self.player.pos);
+            self.wormhole = create_wormholes(1, self.player.pos, &self.rocks, self.screen_width, self.screen_height);
+        }
+        self.rock_spawn_timer = BELT_DRIP_INTERVAL;
+        self.rock_ledger = RockLedger::new(self.rocks.len());
+        self.waypoints = Vec::new();
+        let distance = self.wormhole_distance();
+        self.wormhole_distances.push(distance);
+        self.satellites = if self.level >= SATELLITE_MIN_LEVEL {
+            let num = if self.level >= SATELLITE_MIN_LEVEL + 2 { 2 } else { 1 };
+            create_satellites(num, self.wormhole[0].pos)
+        } else {
+            Vec::new()
+        };
+        self.warps = if self.level >= WARP_MIN_LEVEL {
+            let num = if self.level >= WARP_MIN_LEVEL + 2 { 2 } else { 1 };
+            create_warps(num, self.player.pos, &self.rocks, self.screen_width, self.screen_height)
+        } else {
+            Vec::new()
+        };
+        self.pickups = create_pickups(self.player.pos);
+    }
+
+    /// Saves anything worth keeping before the game closes. Every
+    /// way of quitting has to go through here.
+    fn on_exit(&mut self) {
+        // Finished runs were logged when the ship died.
+        if self.player.life > 0.0 && !self.time_up() && self.run_stats.time > 0.0 {
+            self.log_run();
+        }
+        if let Some(rank) = self.submit_score() {
+            if rank == 0 {
+                println!("New high score: {}", self.final_score());
+            }
+            self.save_scores();
+        }
+    }
+
+    fn save_scores(&self) {
+        if self.persist {
+            self.high_scores.save();
+        }
+    }
+
+    fn log_run(&self) {
+        if !self.persist {
+            return;
+        }
+        let rocks = RockLedger::total(self.rock_ledgers.iter().chain(Some(&self.rock_ledger)));
+        runlog::append_run(&runlog::RunRecord {
+            mode: &self.score_key(),
+            score: self.final_score(),
+            level: self.level,
+            rocks_destroyed: rocks.destroyed(),
+            accuracy: self.run_stats.accuracy(),
+            duration: self.run_stats.time,
+            grades: &self.level_grades.iter().collect::<String>(),
+            wormhole_distances: &self.wormhole_distances,
+            rocks_initial: rocks.spawned_initial,
+            rocks_later: rocks.spawned_later,
+            rock_fragments: rocks.fragments,
+            rocks_shot: rocks.destroyed_by_shot,
+            rocks_blasted: rocks.destroyed_by_blast,
+            rocks_rammed: rocks.destroyed_by_ram,
+            peak_rocks: rocks.peak,
+        });
+    }
+
+    /// What this run's high score is kept under. Timed runs are
+    /// ranked separately for each time limit.
+    fn score_key(&self) -> String {
+        match self.time_left {
+            Some(_) => format!("{}-timed-{:.0}s", self.mode.id(), self.tunables.time_limit),
+            None => self.mode.id().to_string(),
+        }
+    }
+
+    /// Which of the mode's boards this run is ranked on.
+    fn difficulty(&self) -> Difficulty {
+        Difficulty::from_multiplier(self.mutators.score_multiplier())
+    }
+
+    /// Puts the run on its leaderboard, or updates it if it's already
+    /// there, returning its rank if it made it on.
+    fn submit_score(&mut self) -> Option<usize> {
+        let entry = ScoreEntry {
+            score: self.final_score(),
+            level: self.level,
+            time: self.run_stats.time,
+            started: self.run_started.clone(),
+            mutators: self.mutators.names().iter().map(|name| name.to_string()).collect(),
+        };
+        let (mode, difficulty) = (self.score_key(), self.difficulty());
+        self.high_scores.submit(&mode, difficulty, entry)
+    }
+
+    /// Whether the run's time limit has run out.
+    fn time_up(&self) -> bool {
+        self.time_left.is_some_and(|t| t <= 0.0)
+    }
+
+    /// The score with the mutators' multiplier applied.
+    fn final_score(&self) -> i32 {
+        (self.score as f32 * self.mutators.score_multiplier()).round() as i32
+    }
+
+    /// With the fog mutator on, marks everything close to the
+    /// ship or reached by a radar pulse as seen just now.
+    fn update_fog(&mut self) {
+        if !self.mutators.fog {
+            return;
+        }
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        let now = self.run_stats.time;
+        let player = self.player.pos;
+        let radar = &self.radar;
+        let in_sight = |pos: Point2| {
+            wrapped_offset(player, pos, sx, sy).len() <= FOG_RADIUS
+                || radar.iter().any(|r| radar_reaches(r, pos, sx, sy))
+        };
+        for actor in self.rocks.iter_mut().chain(&mut self.wormhole).chain(&mut self.satellites) {
+            if in_sight(actor.pos) {
+                actor.last_seen = now;
+            }
+        }
+    }
+
+    /// How clearly the ship can see `actor`, from 1 for in plain
+    /// sight down to 0 once it has faded back into the fog.
+    /// Everything is in plain sight without the fog mutator.
+    fn visibility(&self, actor: &Actor) -> f32 {
+        if !self.mutators.fog {
+            return 1.0;
+        }
+        (1.0 - (self.run_stats.time - actor.last_seen) / FOG_FADE_TIME).clamp(0.0, 1.0)
+    }
+
+    /// The wrapped offset to the closest rock or satellite the ship
+    /// can see, with the color its compass marker is drawn in.
+    /// Anything hidden behind a rock is left out.
+    fn nearest_threat(&self) -> Option<(Vector2, Color)> {
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        let player = self.player.pos;
+        let rocks = self
+            .rocks
+            .iter()
+            .enumerate()
+            // Nothing hidden by the fog gives itself away.
+            .filter(|(_, r)| self.visibility(r) > 0.0)
+            .filter(|(i, r)| line_of_sight(player, r.pos, &self.rocks, &[*i], sx, sy))
+            .map(|(_, r)| (r, Color::RED));
+        let satellites = self
+            .satellites
+            .iter()
+            .filter(|s| self.visibility(s) > 0.0)
+            .filter(|s| line_of_sight(player, s.pos, &self.rocks, &[], sx, sy))
+            .map(|s| (s, Color::ORANGE));
+        rocks
+            .chain(satellites)
+            .map(|(actor, color)| (wrapped_offset(player, actor.pos, sx, sy), color))
+            .min_by(|(a, _), (b, _)| a.len().partial_cmp(&b.len()).unwrap())
+    }
+
+    /// How far the player is from the wormhole, across the wrap.
+    fn wormhole_distance(&self) -> f32 {
+        match self.wormhole.first() {
+            Some(w) => wrapped_offset(self.player.pos, w.pos, self.screen_width, self.screen_height).len(),
+            None => 0.0,
+        }
+    }
+
+    /// Fades the screen out, makes the change, then fades back in.
+    /// Puts a new ship in the middle of the field after one was
+    /// lost, with the rocks around it cleared away and a moment
+    /// where nothing can hurt it. The level and score carry on.
+    fn respawn(&mut self) {
+        self.player = create_player();
+        let (sx, sy) = (self.screen_width, self.screen_height);
+        let center = self.player.pos;
+        self.rocks.retain(|r| wrapped_offset(center, r.pos, sx, sy).len() > RESPAWN_CLEAR_RADIUS);
+        self.shots = Vec::new();
+        self.ram_timer = 0.0;
+        self.player_thrust_time = 0.0;
+        self.energy = MAX_ENERGY;
+        self.invuln_timer = INVULN_TIME;
+        self.last_stand_time = 0.0;
+        // Reaching the wormhole and dying at the same moment
+        // still counts as getting through.
+        if self.level_cleared {
+            self.next_level();
+        }
+    }
+
+    /// Whether the ship flying is the last one.
+    fn on_last_stand(&self) -> bool {
+        self.mode != RunMode::Practice && self.lives == 1
+    }
+
+    /// Counts how long the last ship has lasted, and gives a spare
+    /// back once it's held on long enough, but only once a level.
+    fn update_last_stand(&mut self, dt: f32) {
+        if !self.on_last_stand() {
+            self.last_stand_time = 0.0;
+            return;
+        }
+        self.last_stand_time += dt;
+        if !self.last_stand_used && self.last_stand_time >= LAST_STAND_TIME {
+            self.last_stand_used = true;
+            self.lives += 1;
+            println!("Held on, ship recovered");
+            if self.tunables.ticker {
+                self.ticker.push("Held on +1 ship".to_string());
+            }
+        }
+    }
+
+    fn start_transition(&mut self, change: ScreenChange) {
+        self.pending_change = Some(change);
+    }
+
+    fn in_transition(&self) -> bool {
+        self.pending_change.is_some() || self.transition_alpha > 0.0
+    }
+
+    /// Advances any fade in progress, returning true while
+    /// the world should stay frozen.
+    fn update_transition(&mut self, dt: f32) -> bool {
+        if let Some(change) = self.pending_change {
+            self.transition_alpha += dt / FADE_TIME;
+            if self.transition_alpha >= 1.0 {
+                self.transition_alpha = 1.0;
+                // The level complete screen stays up a moment once it's faded in.
+                if change == ScreenChange::NextLevel && self.level_complete_time > 0.0 {
+                    self.level_complete_time -= dt;
+                    return true;
+                }
+                self.pending_change = None;
+                match change {
+                    ScreenChange::NextLevel => self.next_level(),
+                    ScreenChange::Respawn => self.respawn(),
+                    ScreenChange::Restart => self.reset(),
+                }
+            }
+            true
+        } else if self.transition_alpha > 0.0 {
+            self.transition_alpha = (self.transition_alpha - dt / FADE_TIME).max(0.0);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Counts the frame about to be drawn. While paused nothing moves,
+    /// so only a frame asked for with `.` counts as a new one. There's
+    /// nothing drawn between updates to interpolate, so it's the same
+    /// picture, it just lets a capture line up on an exact frame.
+    fn count_render_frame(&mut self) {
+        if !self.debug_paused || std::mem::take(&mut self.debug_frame_step) {
+            self.render_frames += 1;
+        }
+    }
+
+    /// Runs the updates owed for `elapsed` seconds of real time, a
+    /// fixed step at a time.
+    fn run_updates(&mut self, elapsed: f32) {
+        let seconds = 1.0 / (DESIRED_FPS as f32);
+        let advance = self.timestep.advance(elapsed);
+        if advance.dropped > 0.0 {
+            self.frames_behind += 1;
+            if cfg!(feature = "debug-tools") {
+                println!("Simulation fell behind, dropped {:.0} ms", advance.dropped * 1000.0);
+            }
+        }
+        for _ in 0..advance.steps {
+            if self.quit_hold.update(seconds) {
+                self.on_exit();
+                std::process::exit(0);
+            }
+            if self.save_menu.as_mut().is_some_and(|menu| menu.confirm.update(seconds)) {
+                self.finish_import();
+            }
+            self.step(seconds);
+        }
+    }
+
+    /// Runs the game forward one frame of `seconds`, unless it's
+    /// paused and a single step wasn't asked for.
+    fn step(&mut self, seconds: f32) {
+        if self.debug_paused && !std::mem::take(&mut self.debug_step) {
+            return;
+        }
+        if self.pack_picker.is_some() || self.leaderboard.is_some() || self.save_menu.is_some() {
+            return;
+        }
+        self.sim_steps += 1;
+        self.cues.update(seconds);
+        self.ticker.update(seconds);
+        let hud_target = system_color(&self.player.sys, &self.tunables.hud_colors);
+        self.hud_color = lerp_color(self.hud_color, hud_target, (seconds / HUD_COLOR_TIME).min(1.0));
+
+        if self.update_transition(seconds) || self.state == GameState::GameOver {
+            return;
+        }
+
+        // The time limit runs on real time, slowmo doesn't stretch it.
+        if let Some(time_left) = &mut self.time_left {
+            *time_left -= seconds;
+        }
+
+        // Give the player a moment to reorient after switching systems.
+        let seconds = if self.switch_slowmo_timer > 0.0 {
+            self.switch_slowmo_timer -= seconds;
+            seconds * SWITCH_SLOWMO_SCALE
+        } else {
+            seconds
+        };
+        let seconds = if self.aiming {
+            seconds * AIM_SLOWMO_SCALE
+        } else {
+            seconds
+        };
+        self.system_time += seconds;
+        self.ram_timer = (self.ram_timer - seconds).max(0.0);
+        self.invuln_timer = (self.invuln_timer - seconds).max(0.0);
+        self.update_last_stand(seconds);
+        self.run_stats.time += seconds;
+
+        self.energy = (self.energy + ENERGY_REGEN * seconds).min(MAX_ENERGY);
+        self.energy_flash -= seconds;
+
+        // Update the player state based on the user input.
+        self.actions = Actions::derive(&self.input, &self.player.sys, self.tunables.fire_and_thrust);
+        if self.actions.yaxis > 0.0 && !self.spend_energy(THRUST_ENERGY_COST * self.actions.yaxis * seconds) {
+            self.actions.yaxis = 0.0;
+        }
+        if self.actions.yaxis > 0.0 {
+            self.player_thrust_time += seconds;
+        } else {
+            self.player_thrust_time = 0.0;
+        }
+        let mut thrust = PLAYER_THRUST * self.tunables.thrust_curve.factor(self.player_thrust_time);
+        if self.player.sys == Systems::Engines && self.system_bonus() {
+            thrust *= ENGINES_BONUS_THRUST;
+        }
+        // Doing both at once takes its toll on the engines.
+        if self.actions.fire && self.actions.yaxis > 0.0 {
+            thrust *= 1.0 - self.tunables.fire_thrust_penalty.clamp(0.0, 1.0);
+        }
+        player_handle_input(&mut self.player, &self.actions, thrust, seconds);
+        self.player_shot_timeout -= seconds;
+        self.player_repulse_timeout -= seconds;
+        self.repulse_flash -= seconds;
+        // With hold to aim, holding fire lines the shot up
+        // and letting go fires it.
+        let (fire, aimed) = match self.tunables.fire_control {
+            FireControl::Auto => (self.actions.fire, false),
+            FireControl::HoldToAim => {
+                let released = self.aiming && !self.actions.fire && self.player.sys == Systems::Wepons;
+                self.aiming = self.actions.fire;
+                (released, true)
+            }
+        };
+        if fire {
+            if self.weapon_mode == WeaponMode::Repulsor {
+                if self.player_repulse_timeout < 0.0 && self.spend_energy(REPULSE_ENERGY_COST) {
+                    self.fire_player_repulsor();
+                }
+            } else if self.player_shot_timeout < 0.0 && self.spend_energy(SHOT_ENERGY_COST) {
+                self.fire_player_shot(aimed);
+            }
+        }
+        self.player_radar_timeout -= seconds;
+        if self.actions.radar && self.player_radar_timeout < 0.0 && self.spend_energy(RADAR_ENERGY_COST) {
+            self.fire_player_radar();
+        }
+
+        // Update the physics for all actors.
+        // Everything that flies freely moves in `physics_substeps`
+        // smaller steps with collisions checked after each one, so
+        // fast shots can't skip over a rock between updates.
+        let substeps = self.tunables.physics_substeps.max(1);
+        let step = seconds / substeps as f32;
+        for _ in 0..substeps {
+            // First the player...
+            update_actor_position(&mut self.player, step);
+            if self.mutators.no_wrap {
+                clamp_actor_position(&mut self.player, self.screen_width, self.screen_height);
+            } else {
+                wrap_actor_position(
+                    &mut self.player,
+                    self.screen_width as f32,
+                    self.screen_height as f32,
+                );
+            }
+
+            // Then the shots...
+            for act in &mut self.shots {
+                update_actor_position(act, step);
+                wrap_actor_position(act, self.screen_width as f32, self.screen_height as f32);
+            }
+
+            // And the rocks.
+            for act in &mut self.rocks {
+                update_actor_position(act, step);
+                wrap_actor_position(act, self.screen_width as f32, self.screen_height as f32);
+            }
+
+            // The wormhole drifts slowly too.
+            for act in &mut self.wormhole {
+                update_actor_position(act, step);
+                wrap_actor_position(act, self.screen_width, self.screen_height);
+            }
+
+            self.handle_collisions();
+        }
+
+        // Shots that run out of time without hitting anything missed.
+        let mut missed_shots = 0;
+        for act in &mut self.shots {
+            let was_alive = act.life > 0.0;
+            handle_timed_life(act, seconds);
+            handle_shot_arming(act, seconds);
+            if was_alive && act.life <= 0.0 && !act.hit {
+                missed_shots += 1;
+            }
+        }
+
+        // And radar
+        for act in &mut self.radar {
+            handle_timed_life(act, seconds);
+        }
+
+        // And blasts
+        for act in &mut self.blasts {
+            handle_timed_life(act, seconds);
+        }
+
+        // New wormholes need to charge up, and
+        // warps again after every trip.
+        for act in self.wormhole.iter_mut().chain(&mut self.warps) {
+            handle_wormhole_charge(act, seconds);
+        }
+
+        if self.tunables.docking {
+            self.update_docking(seconds);
+        }
+
+        self.update_fog();
+
+        // Satellites stay locked to the wormhole's orbit.
+        if let Some(wormhole) = self.wormhole.first() {
+            for act in &mut self.satellites {
+                update_orbit(act, wormhole.pos, seconds);
+                wrap_actor_position(act, self.screen_width, self.screen_height);
+            }
+        }
+
+        // Handle the results of things moving:
+        // object death, and if we have made it
+        // through the wormhole, the next level.
+        self.clear_dead_stuff();
+        self.rock_ledger.peak = self.rock_ledger.peak.max(self.rocks.len());
+
+        // self.check_for_level_respawn();
+        match self.mode {
+            RunMode::Campaign => {
+                self.update_belt(seconds);
+                self.check_for_level_end();
+            }
+            RunMode::Practice => self.update_practice(seconds, missed_shots),
+            RunMode::Endless => self.update_endless(seconds),
+        }
+        // Finally we check for our end state: losing a ship
+        // with more to spare, or the end of the run.
+        let time_up = self.time_up();
+        if self.player.life <= 0.0 && !time_up && self.lives > 1 {
+            self.lives -= 1;
+            println!("Ship lost, {} left", self.lives);
+            if self.tunables.ticker {
+                self.ticker.push(format!("Ship lost, {} left", self.lives));
+            }
+            self.start_transition(ScreenChange::Respawn);
+        } else if self.player.life <= 0.0 || time_up {
+            if time_up {
+                println!("Time's up!");
+            }
+            println!("Your score was {}", self.final_score());
+            println!("Your level was {}", self.level);
+            let distances: Vec<String> = self.wormhole_distances.iter().map(|d| format!("{:.0}", d)).collect();
+            println!("Wormhole distances: {}", distances.join(", "));
+            for (level, ledger) in self.rock_ledgers.iter().chain(Some(&self.rock_ledger)).enumerate() {
+                println!("Level {} rocks: {}", level, ledger);
+            }
+            if let Some(rank) = self.submit_score() {
+                if rank == 0 {
+                    println!("New high score!");
+                } else {
+                    println!("Number {} on the leaderboard", rank + 1);
+                }
+                self.save_scores();
+            }
+            println!("High score: {}", self.high_scores.best(&self.score_key(), self.difficulty()));
+            self.log_run();
+            self.state = GameState::GameOver;
+            // Nothing else happens to this run, even if we made it
+            // into the wormhole on the way out.
+            self.pending_change = None;
+        }
+    }
+}
+
+/// **********************************************************************
+/// A couple of utility functions.
+/// **********************************************************************
+
+fn print_instructions() {
+    println!();
+    println!("Welcome to Systems Critical");
+    println!();
+    println!("How to play:");
+    println!("Switch ship systems with 1,2,3");
+    println!("1 engines: you can move forward with w");
+    println!("2 wepons: fire wepons with w");
+    println!("3 rader: scan the surronding area with w");
+    println!("a,d or the arrow keys: turn left and right");
+    println!("up arrow: thrust while using wepons, if fire_and_thrust is on in tunables.ron");
+    println!("q: switch wepon mode (in detonate mode press w again to set off your shots,");
+    println!("   the repulsor pushes astroids away without destroying them,");
+    println!("   piercing shots go through astroids)");
+    println!("t: drop a waypoint, or pick one up when you're on it");
+    println!("p: switch asset pack");
+    println!("m: switch between the campaign, target practice and endless");
+    println!("hold esc: quit");
+    println!();
+}
+
+/// Dark colors the background cycles through as the levels go up.
+/// They're kept dark so everything drawn over them stands out.
+const LEVEL_BACKGROUNDS: [(u8, u8, u8); 5] = [
+    (0, 0, 0),
+    (6, 8, 30),
+    (22, 4, 28),
+    (2, 22, 18),
+    (28, 10, 4),
+];
+
+fn level_background(level: i32) -> Color {
+    let (r, g, b) = LEVEL_BACKGROUNDS[level.rem_euclid(LEVEL_BACKGROUNDS.len() as i32) as usize];
+    Color::from_rgba(r, g, b, 1.0)
+}
+
+// Seconds the HUD takes to change color after switching systems.
+const HUD_COLOR_TIME: f32 = 0.25;
+
+/// The HUD color for the system the ship is running.
+fn system_color(sys: &Systems, colors: &HudColors) -> Color {
+    let (r, g, b) = match sys {
+        Systems::Engines => colors.engines,
+        Systems::Wepons => colors.wepons,
+        Systems::Radar => colors.radar,
+    };
+    Color::from_rgba(r, g, b, 1.0)
+}
+
+/// Moves `from` a fraction `t` of the way to `to`.
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    Color {
+        r: from.r + (to.r - from.r) * t,
+        g: from.g + (to.g - from.g) * t,
+        b: from.b + (to.b - from.b) * t,
+        a: from.a + (to.a - from.a) * t,
+    }
+}
+
+/// `background` is the color behind the actor, which hollow
+/// shapes like the radar rings are cut out with. `tint` colors
+/// the actor's sprite, if it has one, and its alpha fades the
+/// wormhole and satellites.
+fn draw_actor(
+    assets: &mut Assets,
+    window: &mut Window,
+    actor: &Actor,
+    world_coords: (f32, f32),
+    background: Color,
+    tint: Option<Color>,
+) -> quicksilver::Result<()> {
+    let (screen_w, screen_h) = world_coords;
+    let pos = world_to_screen_coords(screen_w, screen_h, actor.pos);
+    let fade = tint.map_or(1.0, |t| t.a);
+    if actor.tag == ActorType::Radar {
+        let scale = ((RADAR_LIFE - actor.life).trunc() + (RADAR_LIFE - actor.life + 1.).fract()) * 10. * actor.charge;
+        let transform = geom::Transform::scale((scale, scale));
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), 16),
+            Background::Col(Color::GREEN),
+            transform,
+            actor.layer,
+        );
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), 15),
+            Background::Col(background),
+            transform,
+            actor.layer + 1,
+        );
+        Ok(())
+    } else if actor.tag == ActorType::Wormhole || actor.tag == ActorType::Warp {
+        // Grows and brightens as it charges up.
+        let size = 0.3 + 0.7 * actor.charge;
+        let color = if actor.tag == ActorType::Warp {
+            Color::CYAN
+        } else {
+            Color::PURPLE
+        };
+        let color = color.with_alpha((0.4 + 0.6 * actor.charge) * fade);
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), 14. * size),
+            Background::Col(color),
+            geom::Transform::IDENTITY,
+            actor.layer,
+        );
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), 12. * size),
+            Background::Col(background),
+            geom::Transform::IDENTITY,
+            actor.layer,
+        );
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), 2),
+            Background::Col(color),
+            geom::Transform::IDENTITY,
+            actor.layer,
+        );
+        Ok(())
+    } else if actor.tag == ActorType::Pickup {
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), actor.bbox_size),
+            Background::Col(Color::CYAN),
+            geom::Transform::IDENTITY,
+            actor.layer,
+        );
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), actor.bbox_size * 0.6),
+            Background::Col(background),
+            geom::Transform::IDENTITY,
+            actor.layer + 1,
+        );
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), actor.bbox_size * 0.3),
+            Background::Col(Color::CYAN),
+            geom::Transform::IDENTITY,
+            actor.layer + 2,
+        );
+        Ok(())
+    } else if actor.tag == ActorType::Satellite {
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), actor.bbox_size),
+            Background::Col(Color::ORANGE.with_alpha(fade)),
+            geom::Transform::IDENTITY,
+            actor.layer,
+        );
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), actor.bbox_size / 2.0),
+            Background::Col(background),
+            geom::Transform::IDENTITY,
+            actor.layer + 1,
+        );
+        Ok(())
+    } else if let Some(image) = assets.actor_image(actor) {
+        image.execute(|i| {
+            let scale = if actor.tag == ActorType::Rock {
+                actor.bbox_size / ROCK_BBOX
+            } else {
+                1.0
+            };
+            let transform = geom::Transform::rotate(actor.facing * 180.0 * std::f32::consts::FRAC_1_PI)
+                * geom::Transform::scale((scale, scale));
+            let target_rect = i.area().with_center((pos.x, pos.y));
+            let background = match tint {
+                Some(color) => Background::Blended(&i, color),
+                None => Background::Img(&i),
+            };
+            window.draw_ex(
+                &target_rect,
+                background,
+                transform,
+                actor.layer,
+            );
+            Ok(())
+        })
+    } else {
+        // Placeholder for art that couldn't be loaded.
+        window.draw_ex(
+            &geom::Circle::new((pos.x, pos.y), actor.bbox_size),
+            Background::Col(Color::WHITE),
+            geom::Transform::IDENTITY,
+            actor.layer,
+        );
+        Ok(())
+    }
+}
+
+/// Draws a marker next to the player pointing the
+/// shortest way to the wormhole.
+fn draw_wormhole_indicator(window: &mut Window, player: &Actor, wormhole: &Actor, world_coords: (f32, f32)) {
+    let (screen_w, screen_h) = world_coords;
+    let offset = wrapped_offset(player.pos, wormhole.pos, screen_w, screen_h);
+    if offset.len() == 0.0 {
+        return;
+    }
+    let dir = offset.normalize();
+    let side = Vector2::new(-dir.y, dir.x);
+    let tip = player.pos + dir * 36.0;
+    let base = player.pos + dir * 26.0;
+    window.draw_ex(
+        &geom::Triangle::new(
+            world_to_screen_coords(screen_w, screen_h, tip),
+            world_to_screen_coords(screen_w, screen_h, base + side * 6.0),
+            world_to_screen_coords(screen_w, screen_h, base - side * 6.0),
+        ),
+        Background::Col(Color::PURPLE),
+        geom::Transform::IDENTITY,
+        510,
+    );
+}
+
+/// Draws a line straight out from the ship while aiming.
+fn draw_aim_line(window: &mut Window, player: &Actor, world_coords: (f32, f32)) {
+    let (screen_w, screen_h) = world_coords;
+    let dir = vec_from_angle(player.facing);
+    window.draw_ex(
+        &geom::Line::new(
+            world_to_screen_coords(screen_w, screen_h, player.pos + dir * player.bbox_size),
+            world_to_screen_coords(screen_w, screen_h, player.pos + dir * AIM_LINE_LENGTH),
+        ),
+        Background::Col(Color::WHITE.with_alpha(0.5)),
+        geom::Transform::IDENTITY,
+        510,
+    );
+}
+
+// How many seconds ahead the radar's velocity arrows reach.
+const RADAR_ARROW_TIME: f32 = 0.75;
+
+/// Draws an arrow from a rock along where it's heading, for rocks
+/// the radar has picked up. `alpha` fades it with the pulse.
+fn draw_velocity_arrow(window: &mut Window, rock: &Actor, world_coords: (f32, f32), alpha: f32) {
+    let (screen_w, screen_h) = world_coords;
+    let travel = rock.velocity * RADAR_ARROW_TIME;
+    if travel.len() < 1.0 {
+        return;
+    }
+    let dir = travel.normalize();
+    let side = Vector2::new(-dir.y, dir.x);
+    let start = rock.pos + dir * rock.bbox_size;
+    let tip = start + travel;
+    let color = Color::GREEN.with_alpha(alpha);
+    window.draw_ex(
+        &geom::Line::new(
+            world_to_screen_coords(screen_w, screen_h, start),
+            world_to_screen_coords(screen_w, screen_h, tip),
+        )
+        .with_thickness(2.0),
+        Background::Col(color),
+        geom::Transform::IDENTITY,
+        510,
+    );
+    window.draw_ex(
+        &geom::Triangle::new(
+            world_to_screen_coords(screen_w, screen_h, tip + dir * 6.0),
+            world_to_screen_coords(screen_w, screen_h, tip + side * 4.0),
+            world_to_screen_coords(screen_w, screen_h, tip - side * 4.0),
+        ),
+        Background::Col(color),
+        geom::Transform::IDENTITY,
+        510,
+    );
+}
+
+/// Draws spikes sticking out all around the ship while the ram
+/// shield is up, flickering when it's about to run out.
+fn draw_ram_aura(window: &mut Window, player: &Actor, time_left: f32, world_coords: (f32, f32), reduce_motion: bool) {
+    const SPIKES: i32 = 8;
+    let warning = time_left < RAM_WARNING_TIME;
+    if warning && !reduce_motion && (time_left * 8.0) as i32 % 2 == 0 {
+        return;
+    }
+    // Without motion it holds still and dims instead of flickering.
+    let alpha = if warning && reduce_motion { 0.4 } else { 1.0 };
+    let (screen_w, screen_h) = world_coords;
+    let inner = player.bbox_size + 2.0;
+    let outer = player.bbox_size + 10.0;
+    let spin = if reduce_motion { 0.0 } else { time_left * 2.0 };
+    for i in 0..SPIKES {
+        let angle = spin + 2.0 * std::f32::consts::PI * i as f32 / SPIKES as f32;
+        let dir = vec_from_angle(angle);
+        let side = Vector2::new(-dir.y, dir.x);
+        window.draw_ex(
+            &geom::Triangle::new(
+                world_to_screen_coords(screen_w, screen_h, player.pos + dir * outer),
+                world_to_screen_coords(screen_w, screen_h, player.pos + dir * inner + side * 4.0),
+                world_to_screen_coords(screen_w, screen_h, player.pos + dir * inner - side * 4.0),
+            ),
+            Background::Col(Color::CYAN.with_alpha(alpha)),
+            geom::Transform::IDENTITY,
+            505,
+        );
+    }
+}
+
+/// Draws a blast as an orange ball that grows as it fades, or
+/// with reduced motion one that's full size from the start.
+fn draw_blast(window: &mut Window, blast: &Actor, world_coords: (f32, f32), reduce_motion: bool) {
+    let (screen_w, screen_h) = world_coords;
+    let pos = world_to_screen_coords(screen_w, screen_h, blast.pos);
+    let radius = if reduce_motion { BLAST_RADIUS } else { blast_radius(blast) };
+    let alpha = blast.life / BLAST_LIFE;
+    window.draw_ex(
+        &geom::Circle::new((pos.x, pos.y), radius),
+        Background::Col(Color::ORANGE.with_alpha(alpha)),
+        geom::Transform::IDENTITY,
+        blast.layer,
+    );
+    window.draw_ex(
+        &geom::Circle::new((pos.x, pos.y), radius * 0.6),
+        Background::Col(Color::YELLOW.with_alpha(alpha)),
+        geom::Transform::IDENTITY,
+        blast.layer + 1,
+    );
+}
+
+/// Draws a still glow behind a rock a radar pulse has reached,
+/// used in place of the growing ring with reduced motion.
+fn draw_contact_highlight(window: &mut Window, rock: &Actor, world_coords: (f32, f32), alpha: f32) {
+    let (screen_w, screen_h) = world_coords;
+    let pos = world_to_screen_coords(screen_w, screen_h, rock.pos);
+    window.draw_ex(
+        &geom::Circle::new((pos.x, pos.y), rock.bbox_size + 6.0),
+        Background::Col(Color::GREEN.with_alpha(0.4 * alpha)),
+        geom::Transform::IDENTITY,
+        rock.layer - 1,
+    );
+}
+
+/// Draws a red glow around the edges of the screen while the last
+/// ship is flying. It beats like a heart unless motion is reduced.
+fn draw_last_stand_edge(window: &mut Window, time: f32, world_coords: (f32, f32), reduce_motion: bool) {
+    let (screen_w, screen_h) = world_coords;
+    let alpha = if reduce_motion {
+        0.25
+    } else {
+        0.2 + 0.15 * (time * LAST_STAND_PULSE_RATE * 2.0 * std::f32::consts::PI).sin()
+    };
+    let edges = [
+        geom::Rectangle::new((0.0, 0.0), (screen_w, LAST_STAND_EDGE)),
+        geom::Rectangle::new((0.0, screen_h - LAST_STAND_EDGE), (screen_w, LAST_STAND_EDGE)),
+        geom::Rectangle::new((0.0, LAST_STAND_EDGE), (LAST_STAND_EDGE, screen_h - 2.0 * LAST_STAND_EDGE)),
+        geom::Rectangle::new((screen_w - LAST_STAND_EDGE, LAST_STAND_EDGE), (LAST_STAND_EDGE, screen_h - 2.0 * LAST_STAND_EDGE)),
+    ];
+    for edge in &edges {
+        window.draw_ex(
+            edge,
+            Background::Col(Color::RED.with_alpha(alpha)),
+            geom::Transform::IDENTITY,
+            HUD_LAYER - 1,
+        );
+    }
+}
+
+// The most waypoints out at once, the oldest goes when another is placed.
+const MAX_WAYPOINTS: usize = 3;
+// How close the ship has to be to a waypoint to pick it back up.
+const WAYPOINT_REMOVE_RADIUS: f32 = 30.0;
+
+/// Draws a waypoint as a small diamond, underneath the actors.
+fn draw_waypoint(window: &mut Window, pos: Point2, world_coords: (f32, f32)) {
+    let (screen_w, screen_h) = world_coords;
+    let point = |x: f32, y: f32| world_to_screen_coords(screen_w, screen_h, pos + Vector2::new(x, y));
+    for tip in &[8.0, -8.0] {
+        window.draw_ex(
+            &geom::Triangle::new(point(-6.0, 0.0), point(0.0, *tip), point(6.0, 0.0)),
+            Background::Col(Color::WHITE.with_alpha(0.6)),
+            geom::Transform::IDENTITY,
+            485,
+        );
+    }
+}
+
+// Radius of the threat compass ring around the ship.
+const COMPASS_RADIUS: f32 = 44.0;
+
+/// Draws a faint ring around the ship with a marker on it pointing
+/// at `offset`, the wrapped offset to the nearest threat.
+fn draw_threat_compass(window: &mut Window, player: &Actor, offset: Vector2, color: Color, world_coords: (f32, f32)) {
+    const SEGMENTS: i32 = 24;
+    let (screen_w, screen_h) = world_coords;
+    let ring = |i: i32| {
+        let angle = 2.0 * std::f32::consts::PI * i as f32 / SEGMENTS as f32;
+        world_to_screen_coords(screen_w, screen_h, player.pos + vec_from_angle(angle) * COMPASS_RADIUS)
+    };
+    for i in 0..SEGMENTS {
+        window.draw_ex(
+            &geom::Line::new(ring(i), ring(i + 1)),
+            Background::Col(Color::WHITE.with_alpha(0.15)),
+            geom::Transform::IDENTITY,
+            510,
+        );
+    }
+    if offset.len() == 0.0 {
+        return;
+    }
+    let dir = offset.normalize();
+    let side = Vector2::new(-dir.y, dir.x);
+    let tip = player.pos + dir * (COMPASS_RADIUS + 6.0);
+    let base = player.pos + dir * (COMPASS_RADIUS - 4.0);
+    window.draw_ex(
+        &geom::Triangle::new(
+            world_to_screen_coords(screen_w, screen_h, tip),
+            world_to_screen_coords(screen_w, screen_h, base + side * 5.0),
+            world_to_screen_coords(screen_w, screen_h, base - side * 5.0),
+        ),
+        Background::Col(color),
+        geom::Transform::IDENTITY,
+        510,
+    );
+}
+
+/// Draws an arc around the wormhole that fills up
+/// as the ship docks with it.
+fn draw_docking_ring(window: &mut Window, wormhole: &Actor, fraction: f32, world_coords: (f32, f32)) {
+    const SEGMENTS: i32 = 24;
+    let (screen_w, screen_h) = world_coords;
+    let radius = wormhole.bbox_size + 8.0;
+    let ring = |i: i32| {
+        let angle = 2.0 * std::f32::consts::PI * i as f32 / SEGMENTS as f32;
+        world_to_screen_coords(screen_w, screen_h, wormhole.pos + vec_from_angle(angle) * radius)
+    };
+    let filled = (fraction * SEGMENTS as f32).ceil() as i32;
+    for i in 0..filled.min(SEGMENTS) {
+        window.draw_ex(
+            &geom::Line::new(ring(i), ring(i + 1)).with_thickness(3.0),
+            Background::Col(Color::PURPLE),
+            geom::Transform::IDENTITY,
+            510,
+        );
+    }
+}
+
+/// Draws the repulsor cone as a fan of triangles, fading out
+/// over `REPULSOR_FLASH_TIME`.
+fn draw_repulse_cone(window: &mut Window, player: &Actor, world_coords: (f32, f32), time_left: f32) {
+    const SEGMENTS: i32 = 8;
+    let (screen_w, screen_h) = world_coords;
+    let color = Color::CYAN.with_alpha(0.4 * time_left / REPULSOR_FLASH_TIME);
+    let apex = world_to_screen_coords(screen_w, screen_h, player.pos);
+    let edge = |i: i32| {
+        let angle = player.facing - REPULSOR_HALF_ANGLE + 2.0 * REPULSOR_HALF_ANGLE * i as f32 / SEGMENTS as f32;
+        world_to_screen_coords(screen_w, screen_h, player.pos + vec_from_angle(angle) * REPULSOR_RANGE)
+    };
+    for i in 0..SEGMENTS {
+        window.draw_ex(
+            &geom::Triangle::new(apex, edge(i), edge(i + 1)),
+            Background::Col(color),
+            geom::Transform::IDENTITY,
+            490,
+        );
+    }
+}
+
+/// Draws a piece of rendered HUD text on the HUD layer.
+fn draw_hud_text(window: &mut Window, area: &geom::Rectangle, text: &Image) {
+    window.draw_ex(area, Background::Img(text), geom::Transform::IDENTITY, HUD_LAYER);
+}
+
+impl State for MainState {
+    fn new() -> quicksilver::Result<Self> {
+        MainState::new()
+    }
+    
+    // Updates are run from `draw`, as many as the time since the last
+    // frame is owed.
+    fn update(&mut self, _window: &mut Window) -> quicksilver::Result<()> {
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event, _window: &mut Window) -> quicksilver::Result<()> {
+        // Closing the window skips the Escape hold.
+        if let Event::Closed = event {
+            self.on_exit();
+            return Ok(());
+        }
+
+        #[cfg(feature = "debug-tools")]
+        self.inspector_event(event, _window);
+
+        // Held keys are always tracked, even mid fade, so
+        // nothing is stuck or missing once it's done.
+        match event {
+            Event::Key(key, ButtonState::Pressed) => self.input.set_key(*key, true),
+            Event::Key(key, ButtonState::Released) => self.input.set_key(*key, false),
+            Event::GamepadAxis(_, GamepadAxis::LeftStickY, value) => self.input.set_thrust_axis(*value),
+            Event::Unfocused => {
+                self.input.release_all();
+                self.quit_hold.release();
+            }
+            _ => (),
+        }
+
+        // Ignore presses mid fade so they can't do
+        // anything to the next screen.
+        if self.in_transition() {
+            match event {
+                Event::Key(Key::Escape, _) => (),
+                Event::Key(_, ButtonState::Pressed) => return Ok(()),
+                _ => (),
+            }
+        }
+
+        // The pack picker takes every key press while it's open.
+        if let Some(picker) = &mut self.pack_picker {
+            match event {
+                Event::Key(Key::Up, ButtonState::Pressed) => picker.previous(),
+                Event::Key(Key::Down, ButtonState::Pressed) => picker.next(),
+                Event::Key(Key::Return, ButtonState::Pressed) | Event::Key(Key::Space, ButtonState::Pressed) => {
+                    let index = picker.selected();
+                    self.pack_picker = None;
+                    if index != self.pack_index {
+                        self.select_asset_pack(index)?;
+                    }
+                }
+                Event::Key(Key::P, ButtonState::Pressed) | Event::Key(Key::Escape, ButtonState::Pressed) => {
+                    self.pack_picker = None;
+                }
+                _ => (),
+            }
+            return Ok(());
+        }
+
+        // So does the leaderboard.
+        if let Some(board) = &mut self.leaderboard {
+            match event {
+                Event::Key(Key::Up, ButtonState::Pressed) | Event::Key(Key::Down, ButtonState::Pressed) => {
+                    board.switch_filter()
+                }
+                Event::Key(Key::A, ButtonState::Pressed) | Event::Key(Key::Left, ButtonState::Pressed) => {
+                    board.previous()
+                }
+                Event::Key(Key::D, ButtonState::Pressed) | Event::Key(Key::Right, ButtonState::Pressed) => board.next(),
+                Event::Key(Key::L, ButtonState::Pressed) | Event::Key(Key::Escape, ButtonState::Pressed) => {
+                    self.leaderboard = None;
+                }
+                _ => (),
+            }
+            return Ok(());
+        }
+
+        // And the save data menu.
+        if let Some(menu) = &mut self.save_menu {
+            if menu.import.is_some() {
+                match event {
+                    Event::Key(Key::Return, ButtonState::Pressed) => menu.confirm.press(),
+                    Event::Key(Key::Return, ButtonState::Released) => menu.confirm.release(),
+                    Event::Key(Key::Escape, ButtonState::Pressed) => {
+                        menu.import = None;
+                        menu.confirm.release();
+                        menu.message = Some("Import cancelled".to_string());
+                    }
+                    _ => (),
+                }
+                return Ok(());
+            }
+            match event {
+                Event::Key(Key::Up, ButtonState::Pressed) => menu.picker.previous(),
+                Event::Key(Key::Down, ButtonState::Pressed) => menu.picker.next(),
+                Event::Key(Key::Return, ButtonState::Pressed) | Event::Key(Key::Space, ButtonState::Pressed) => {
+                    self.save_menu_select();
+                }
+                Event::Key(Key::O, ButtonState::Pressed) | Event::Key(Key::Escape, ButtonState::Pressed) => {
+                    self.save_menu = None;
+                }
+                _ => (),
+            }
+            return Ok(());
+        }
+
+        // The game over screen only waits to go again.
+        if self.state == GameState::GameOver {
+            match event {
+                Event::Key(Key::L, ButtonState::Pressed) => {
+                    self.open_leaderboard();
+                    return Ok(());
+                }
+                Event::Key(Key::Return, ButtonState::Pressed) | Event::Key(Key::Space, ButtonState::Pressed) => {
+                    println!("Try Again");
+                    self.start_transition(ScreenChange::Restart);
+                    return Ok(());
+                }
+                Event::Key(Key::Escape, _) => (),
+                Event::Key(_, ButtonState::Pressed) => return Ok(()),
+                _ => (),
+            }
+        }
+
+        match event {
+            // Buttons pressed
+            Event::Key(Key::Key1, ButtonState::Pressed) => {
+                self.switch_system(Systems::Engines);
+            }
+            Event::Key(Key::Key2, ButtonState::Pressed) => {
+                self.switch_system(Systems::Wepons);
+            }
+            Event::Key(Key::Key3, ButtonState::Pressed) => {
+                self.switch_system(Systems::Radar);
+            }
+            Event::Key(Key::W, ButtonState::Pressed)
+                if self.player.sys == Systems::Wepons
+                    && self.weapon_mode == WeaponMode::Detonate
+                    && self.shots.iter().any(|s| s.armed) =>
+            {
+                self.detonate_shots();
+                self.input.activate_spent = true;
+            }
+            Event::Key(Key::Q, ButtonState::Pressed) => {
+                self.weapon_mode = self.weapon_mode.next();
+                println!("Wepon mode: {:?}", self.weapon_mode);
+            }
+            Event::Key(Key::P, ButtonState::Pressed) => {
+                self.pack_picker = Some(Picker::new(self.packs.len(), self.pack_index));
+            }
+            Event::Key(Key::L, ButtonState::Pressed) => {
+                self.open_leaderboard();
+            }
+            Event::Key(Key::O, ButtonState::Pressed) => {
+                self.save_menu = Some(SaveMenu::new());
+            }
+            Event::Key(Key::T, ButtonState::Pressed) => {
+                self.toggle_waypoint();
+            }
+            Event::Key(Key::M, ButtonState::Pressed) => {
+                self.mode = self.mode.next();
+                println!("Mode: {:?}", self.mode);
+                self.start_transition(ScreenChange::Restart);
+            }
+            Event::Key(Key::F1, ButtonState::Pressed) if cfg!(feature = "debug-tools") => {
+                self.debug_paused = !self.debug_paused;
+            }
+            Event::Key(Key::Comma, ButtonState::Pressed) if self.debug_paused => {
+                self.debug_step = true;
+            }
+            Event::Key(Key::Period, ButtonState::Pressed) if self.debug_paused => {
+                self.debug_frame_step = true;
+            }
+            Event::Key(Key::Escape, ButtonState::Pressed) => {
+                self.quit_hold.press();
+            }
+            // Buttons released
+            Event::Key(Key::Escape, ButtonState::Released) => {
+                self.quit_hold.release();
+            }
+            _ => (), // Do nothing
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, window: &mut Window) -> quicksilver::Result<()> {
+        let now = std::time::Instant::now();
+        let elapsed = self.last_draw.map_or(0.0, |last_draw| now.duration_since(last_draw).as_secs_f32());
+        self.last_draw = Some(now);
+        self.run_updates(elapsed);
+        self.count_render_frame();
+
+        // Clear the screen...
+        let background = level_background(self.level);
+        window.clear(background)?;
+        let reduce_motion = self.tunables.reduce_motion;
+
+        // Worked out up front, the fog needs all of self.
+        let rocks_visible: Vec<f32> = self.rocks.iter().map(|r| self.visibility(r)).collect();
+        let wormhole_visible: Vec<f32> = self.wormhole.iter().map(|w| self.visibility(w)).collect();
+        let satellites_visible: Vec<f32> = self.satellites.iter().map(|s| self.visibility(s)).collect();
+        let faded = |alpha: f32| if alpha < 1.0 { Some(Color::WHITE.with_alpha(alpha)) } else { None };
+
+        // Loop over all objects drawing them...
+        {
+            let assets = &mut self.assets;
+            let coords = (self.screen_width, self.screen_height);
+            let ship_tint = self.tunables.ship_tint.color();
+
+            // A new ship blinks while nothing can hurt it,
+            // or is see through with reduced motion.
+            let p = &self.player;
+            let invulnerable = self.invuln_timer > 0.0;
+            if !invulnerable {
+                draw_actor(assets, window, p, coords, background, ship_tint)?;
+            } else if reduce_motion {
+                let tint = ship_tint.unwrap_or(Color::WHITE).with_alpha(0.5);
+                draw_actor(assets, window, p, coords, background, Some(tint))?;
+            } else if (self.invuln_timer * 8.0) as i32 % 2 == 0 {
+                draw_actor(assets, window, p, coords, background, ship_tint)?;
+            }
+
+            // Shots that can't be set off yet are dimmer.
+            for s in &self.shots {
+                let tint = if s.arming > 0.0 {
+                    Some(ship_tint.unwrap_or(Color::WHITE).with_alpha(0.6))
+                } else {
+                    ship_tint
+                };
+                draw_actor(assets, window, s, coords, background, tint)?;
+            }
+
+            // Rocks that have been hit but not destroyed are tinted.
+            for (r, alpha) in self.rocks.iter().zip(&rocks_visible).filter(|(_, a)| **a > 0.0) {
+                let tint = if rock_damaged(r) {
+                    Some(DAMAGED_ROCK_TINT.with_alpha(*alpha))
+                } else {
+                    faded(*alpha)
+                };
+                draw_actor(assets, window, r, coords, background, tint)?;
+            }
+
+            if !reduce_motion {
+                for r in &self.radar {
+                    draw_actor(assets, window, r, coords, background, None)?;
+                }
+            }
+
+            for b in &self.blasts {
+                draw_blast(window, b, coords, reduce_motion);
+            }
+
+            for (w, alpha) in self.wormhole.iter().zip(&wormhole_visible).filter(|(_, a)| **a > 0.0) {
+                draw_actor(assets, window, w, coords, background, faded(*alpha))?;
+            }
+
+            for w in &self.warps {
+                draw_actor(assets, window, w, coords, background, None)?;
+            }
+
+            for p in &self.pickups {
+                draw_actor(assets, window, p, coords, background, None)?;
+            }
+
+            for (s, alpha) in self.satellites.iter().zip(&satellites_visible).filter(|(_, a)| **a > 0.0) {
+                draw_actor(assets, window, s, coords, background, faded(*alpha))?;
+            }
+        }
+
+        if self.ram_timer > 0.0 {
+            draw_ram_aura(window, &self.player, self.ram_timer, (self.screen_width, self.screen_height), reduce_motion);
+        }
+
+        if self.docking_time > 0.0 {
+            if let Some(wormhole) = self.wormhole.first() {
+                draw_docking_ring(window, wormhole, self.docking_time / DOCKING_TIME, (self.screen_width, self.screen_height));
+            }
+        }
+
+        for w in &self.waypoints {
+            draw_waypoint(window, *w, (self.screen_width, self.screen_height));
+        }
+
+        // Knowing where the wormhole is costs radar time:
+        // the marker only shows while a pulse is out.
+        if !self.radar.is_empty() {
+            if let Some(wormhole) = self.wormhole.first() {
+                draw_wormhole_indicator(window, &self.player, wormhole, (self.screen_width, self.screen_height));
+            }
+        }
+
+        // Rocks a pulse has reached show where they're going
+        // until that pulse fades.
+        for rock in &self.rocks {
+            let alpha = self
+                .radar
+                .iter()
+                .filter(|r| radar_reaches(r, rock.pos, self.screen_width, self.screen_height))
+                .map(|r| r.life / RADAR_LIFE)
+                .fold(0.0, f32::max);
+            if alpha > 0.0 {
+                draw_velocity_arrow(window, rock, (self.screen_width, self.screen_height), alpha);
+                if reduce_motion {
+                    draw_contact_highlight(window, rock, (self.screen_width, self.screen_height), alpha);
+                }
+            }
+        }
+
+        if self.tunables.threat_compass {
+            if let Some((offset, color)) = self.nearest_threat() {
+                draw_threat_compass(window, &self.player, offset, color, (self.screen_width, self.screen_height));
+            }
+        }
+
+        if self.aiming {
+            draw_aim_line(window, &self.player, (self.screen_width, self.screen_height));
+        }
+
+        if self.repulse_flash > 0.0 {
+            draw_repulse_cone(window, &self.player, (self.screen_width, self.screen_height), self.repulse_flash);
+        }
+
+        // And draw the GUI elements in the right places.
+        let level_dest = Point2::new(100.0, 10.0);
+        let score_dest = Point2::new(300.0, 10.0);
+        let best_dest = Point2::new(500.0, 10.0);
+
+        let level_str = if self.mode == RunMode::Endless {
+            format!("Time: {:.0}", self.endless_time)
+        } else {
+            format!("Level: {}", self.level)
+        };
+        let score_str = format!("Score: {}", self.final_score());
+        let lives_dest = Point2::new(300.0, 34.0);
+        let lives_str = if self.mode == RunMode::Practice {
+            None
+        } else {
+            Some(format!("Lives: {}", self.lives))
+        };
+        // The last ship's count flashes red, or stays red with reduced motion.
+        let lives_color = if self.on_last_stand() && (reduce_motion || (self.last_stand_time * 4.0) as i32 % 2 == 0) {
+            Color::RED
+        } else {
+            self.hud_color
+        };
+        let mutators_str = if self.mutators.names().is_empty() {
+            None
+        } else {
+            Some(format!("x{:.2} {}", self.mutators.score_multiplier(), self.mutators.names().join(", ")))
+        };
+        let best_str = format!("Best: {}", self.high_scores.best(&self.score_key(), self.difficulty()));
+        let system_dest = Point2::new(700.0, 10.0);
+        let system_str = format!("{:?}", self.player.sys);
+        let hud_color = self.hud_color;
+        let timer_dest = Point2::new(self.screen_width / 2.0, 45.0);
+        let timer = self.time_left.map(|t| {
+            let t = t.max(0.0).ceil() as i32;
+            let color = if t <= 10 { Color::RED } else { Color::WHITE };
+            (format!("{}:{:02}", t / 60, t % 60), color)
+        });
+        let practice_dest = Point2::new(self.screen_width / 2.0, 80.0);
+        let feedback_dest = Point2::new(self.screen_width / 2.0, 110.0);
+        let practice_str = format!(
+            "Hits: {}  Misses: {}  Average: {:.2}s",
+            self.practice.hits,
+            self.practice.misses,
+            self.practice.average_hit_time()
+        );
+        let feedback_str = if self.practice.feedback_time > 0.0 {
+            Some(&self.practice.feedback)
+        } else {
+            None
+        };
+        let practicing = self.mode == RunMode::Practice;
+        let captions = &self.cues.captions;
+        let (screen_width, screen_height) = (self.screen_width, self.screen_height);
+        let caption_dest = |i: usize| Point2::new(screen_width / 2.0, screen_height - 20.0 - 26.0 * i as f32);
+        let ticker = &self.ticker.entries;
+        let step_str = if cfg!(feature = "debug-tools") {
+            let paused = if self.debug_paused { " (paused)" } else { "" };
+            let behind = if self.frames_behind > 0 {
+                format!(" ({} frames behind)", self.frames_behind)
+            } else {
+                String::new()
+            };
+            Some(format!("Step {} frame {}{}{}", self.sim_steps, self.render_frames, paused, behind))
+        } else {
+            None
+        };
+        let quit_fraction = self.quit_hold.fraction();
+        let quit_dest = Point2::new(self.screen_width / 2.0, self.screen_height / 2.0 - 30.0);
+        // The leaderboard can be opened over the game over screen.
+        let game_over = if self.state == GameState::GameOver && self.leaderboard.is_none() {
+            Some(format!("Score: {}  Level: {}", self.final_score(), self.level))
+        } else {
+            None
+        };
+        let game_over_dest = |offset: f32| Point2::new(screen_width / 2.0, screen_height / 2.0 + offset);
+        let pack_lines: Option<Vec<String>> = self.pack_picker.as_ref().map(|picker| {
+            self.packs
+                .iter()
+                .enumerate()
+                .map(|(i, pack)| {
+                    let marker = if i == picker.selected() { "> " } else { "" };
+                    let author = pack.author.as_ref().map(|a| format!(" by {}", a)).unwrap_or_default();
+                    let in_use = if i == self.pack_index { " (in use)" } else { "" };
+                    format!("{}{}{}{}", marker, pack.name, author, in_use)
+                })
+                .collect()
+        });
+        let board_lines: Option<(Vec<String>, Vec<String>)> = self.leaderboard.as_ref().map(|board| {
+            let marker = |filter| if board.filter() == filter { "> " } else { "" };
+            let filters = vec![
+                format!("{}Mode: < {} >", marker(Filter::Mode), board.mode()),
+                format!("{}Difficulty: < {} >", marker(Filter::Difficulty), board.difficulty().name()),
+            ];
+            let entries = board.entries(&self.high_scores);
+            let mut rows: Vec<String> = entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    let time = entry.time as i32;
+                    // Only the day the run was played.
+                    let date = entry.started.get(..10).unwrap_or("");
+                    format!(
+                        "{}. {}  Level {}  {}:{:02}  {}  {}",
+                        i + 1,
+                        entry.score,
+                        entry.level,
+                        time / 60,
+                        time % 60,
+                        date,
+                        entry.mutators.join(", ")
+                    )
+                })
+                .collect();
+            if rows.is_empty() {
+                rows.push("No scores yet".to_string());
+            }
+            (filters, rows)
+        });
+        let save_lines: Option<(Vec<String>, f32)> = self.save_menu.as_ref().map(|menu| {
+            let mut lines: Vec<String> = match &menu.import {
+                Some(import) => import.summary.clone(),
+                None => SAVE_MENU_ITEMS
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let marker = if i == menu.picker.selected() { "> " } else { "" };
+                        format!("{}{}", marker, item)
+                    })
+                    .collect(),
+            };
+            lines.extend(menu.message.clone());
+            (lines, menu.confirm.fraction())
+        });
+        // Shown over the fade to the next level.
+        let level_complete = match self.level_grades.last() {
+            Some(grade) if self.level_cleared && self.state == GameState::Playing => {
+                let (level_stats, _) = self.level_stats();
+                Some((
+                    format!("LEVEL {} COMPLETE", self.level),
+                    format!(
+                        "{:.0}s, {}/{} hits  Grade: {}",
+                        level_stats.time, level_stats.shots_hit, level_stats.shots_fired, grade
+                    ),
+                ))
+            }
+            _ => None,
+        };
+        let complete_alpha = self.transition_alpha;
+
+        if let Some(font) = &mut self.assets.font {
+            font.execute(|f| {
+                let style = FontStyle::new(24.0, hud_color);
+                let text = f.render(&level_str, &style)?;
+                draw_hud_text(window, &text.area().with_center(level_dest), &text);
+
+                let text = f.render(&score_str, &style)?;
+                draw_hud_text(window, &text.area().with_center(score_dest), &text);
+
+                if let Some(lives_str) = &lives_str {
+                    let text = f.render(lives_str, &FontStyle::new(16.0, lives_color))?;
+                    draw_hud_text(window, &text.area().with_center(lives_dest), &text);
+                }
+
+                let text = f.render(&best_str, &style)?;
+                draw_hud_text(window, &text.area().with_center(best_dest), &text);
+
+                let text = f.render(&system_str, &style)?;
+                draw_hud_text(window, &text.area().with_center(system_dest), &text);
+
+                if let Some(mutators_str) = &mutators_str {
+                    let text = f.render(mutators_str, &FontStyle::new(16.0, Color::WHITE))?;
+                    draw_hud_text(window, &geom::Rectangle::new((10.0, 30.0), text.area().size()), &text);
+                }
+
+                if let Some((timer_str, color)) = &timer {
+                    let text = f.render(timer_str, &FontStyle::new(36.0, *color))?;
+                    draw_hud_text(window, &text.area().with_center(timer_dest), &text);
+                }
+
+                if practicing {
+                    let text = f.render(&practice_str, &style)?;
+                    draw_hud_text(window, &text.area().with_center(practice_dest), &text);
+                    if let Some(feedback) = feedback_str {
+                        let text = f.render(feedback, &style)?;
+                        draw_hud_text(window, &text.area().with_center(feedback_dest), &text);
+                    }
+                }
+
+                // Newest caption at the bottom, fading out.
+                for (i, (cue, time)) in captions.iter().rev().enumerate() {
+                    let (caption, color) = cue.caption();
+                    let style = FontStyle::new(20.0, color.with_alpha(time / CAPTION_TIME));
+                    let text = f.render(caption, &style)?;
+                    draw_hud_text(window, &text.area().with_center(caption_dest(i)), &text);
+                }
+
+                // Ticker in the bottom left, newest at the bottom,
+                // fading out over its last second.
+                for (i, (entry, time)) in ticker.iter().rev().enumerate() {
+                    let style = FontStyle::new(16.0, Color::WHITE.with_alpha(time.min(1.0)));
+                    let text = f.render(entry, &style)?;
+                    let top_left = (10.0, screen_height - 30.0 - 22.0 * i as f32);
+                    draw_hud_text(window, &geom::Rectangle::new(top_left, text.area().size()), &text);
+                }
+
+                if let Some(step_str) = &step_str {
+                    let text = f.render(step_str, &FontStyle::new(16.0, Color::WHITE))?;
+                    let size = text.area().size();
+                    let top_left = (screen_width - size.x - 10.0, screen_height - size.y - 10.0);
+                    draw_hud_text(window, &geom::Rectangle::new(top_left, size), &text);
+                }
+
+                if let Some(result_str) = &game_over {
+                    let text = f.render("GAME OVER", &FontStyle::new(48.0, Color::RED))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(-120.0)), &text);
+                    let text = f.render(result_str, &style)?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(-75.0)), &text);
+                    let text = f.render("Press Enter to play again, L for the leaderboard", &FontStyle::new(20.0, Color::WHITE))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(60.0)), &text);
+                }
+
+                if let Some(lines) = &pack_lines {
+                    let text = f.render("Asset packs", &FontStyle::new(36.0, Color::WHITE))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(-150.0)), &text);
+                    for (i, line) in lines.iter().enumerate() {
+                        let text = f.render(line, &style)?;
+                        draw_hud_text(window, &text.area().with_center(game_over_dest(-100.0 + 30.0 * i as f32)), &text);
+                    }
+                    let hint = "Up and down to choose, Enter to use, P to close";
+                    let text = f.render(hint, &FontStyle::new(16.0, Color::WHITE))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(screen_height / 2.0 - 40.0)), &text);
+                }
+
+                if let Some((lines, _)) = &save_lines {
+                    let text = f.render("Save data", &FontStyle::new(36.0, Color::WHITE))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(-150.0)), &text);
+                    for (i, line) in lines.iter().enumerate() {
+                        let text = f.render(line, &style)?;
+                        draw_hud_text(window, &text.area().with_center(game_over_dest(-100.0 + 30.0 * i as f32)), &text);
+                    }
+                    let hint = format!("Up and down to choose, Enter to pick, O to close ({})", archive::ARCHIVE_FILE);
+                    let text = f.render(&hint, &FontStyle::new(16.0, Color::WHITE))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(screen_height / 2.0 - 40.0)), &text);
+                }
+
+                if let Some((filters, rows)) = &board_lines {
+                    let text = f.render("Leaderboard", &FontStyle::new(36.0, Color::WHITE))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(-230.0)), &text);
+                    for (i, line) in filters.iter().enumerate() {
+                        let text = f.render(line, &style)?;
+                        draw_hud_text(window, &text.area().with_center(game_over_dest(-185.0 + 28.0 * i as f32)), &text);
+                    }
+                    for (i, line) in rows.iter().enumerate() {
+                        let text = f.render(line, &FontStyle::new(20.0, Color::WHITE))?;
+                        draw_hud_text(window, &text.area().with_center(game_over_dest(-115.0 + 26.0 * i as f32)), &text);
+                    }
+                    let hint = "Up and down to choose a filter, A and D to change it, L to close";
+                    let text = f.render(hint, &FontStyle::new(16.0, Color::WHITE))?;
+                    draw_hud_text(window, &text.area().with_center(game_over_dest(screen_height / 2.0 - 40.0)), &text);
+                }
+
+                if let Some((title_str, grade_str)) = &level_complete {
+                    let text = f.render(title_str, &FontStyle::new(36.0, Color::WHITE.with_alpha(complete_alpha)))?;
+                    window.draw_ex(
+                        &text.area().with_center(game_over_dest(-40.0)),
+                        Background::Img(&text),
+                        geom::Transform::IDENTITY,
+                        TRANSITION_LAYER + 2,
+                    );
+                    let text = f.render(grade_str, &FontStyle::new(24.0, Color::WHITE.with_alpha(complete_alpha)))?;
+                    window.draw_ex(
+                        &text.area().with_center(game_over_dest(0.0)),
+                        Background::Img(&text),
+                        geom::Transform::IDENTITY,
+                        TRANSITION_LAYER + 2,
+                    );
+                }
+
+                if quit_fraction > 0.0 {
+                    let text = f.render("Hold Esc to quit", &style)?;
+                    window.draw_ex(
+                        &text.area().with_center(quit_dest),
+                        Background::Img(&text),
+                        geom::Transform::IDENTITY,
+                        TRANSITION_LAYER + 2,
+                    );
+                }
+
+                Ok(())
+            })?;
+        }
+
+        if self.state == GameState::Playing && self.on_last_stand() {
+            draw_last_stand_edge(window, self.last_stand_time, (self.screen_width, self.screen_height), reduce_motion);
+        }
+
+        // The world is dimmed behind the game over screen and the menus.
+        let overlay = self.pack_picker.is_some() || self.leaderboard.is_some() || self.save_menu.is_some();
+        if self.state == GameState::GameOver || overlay {
+            window.draw_ex(
+                &geom::Rectangle::new((0.0, 0.0), (self.screen_width, self.screen_height)),
+                Background::Col(Color::BLACK.with_alpha(0.5)),
+                geom::Transform::IDENTITY,
+                HUD_LAYER - 1,
+            );
+        }
+
+        // Energy between the score and the best score,
+        // flashing red when something needed more.
+        let energy_bar = geom::Rectangle::new((360.0, 6.0), (80.0, 8.0));
+        let energy_color = if self.energy_flash > 0.0 { Color::RED } else { self.hud_color };
         window.draw_ex(
-            &geom::Circle::new((pos.x, pos.y), 12),
-            Background::Col(Color::BLACK),
+            &energy_bar,
+            Background::Col(Color::WHITE.with_alpha(0.3)),
             geom::Transform::IDENTITY,
-            actor.layer,
+            HUD_LAYER,
         );
         window.draw_ex(
-            &geom::Circle::new((pos.x, pos.y), 2),
-            Background::Col(Color::PURPLE),
+            &geom::Rectangle::new(energy_bar.pos, (energy_bar.size.x * self.energy / MAX_ENERGY, energy_bar.size.y)),
+            Background::Col(energy_color),
             geom::Transform::IDENTITY,
-            actor.layer,
+            HUD_LAYER + 1,
         );
-        Ok(())
-    } else {
-        image.execute(|i| {
-            let transform = geom::Transform::rotate(actor.facing * 180.0 * std::f32::consts::FRAC_1_PI);
-            let target_rect = i.area().with_center((pos.x, pos.y));
+
+        // A bar under the system's name fills up towards its bonus.
+        let bonus_fraction = (self.system_time / SYSTEM_BONUS_TIME).min(1.0);
+        let bonus_alpha = if self.system_bonus() { 1.0 } else { 0.5 };
+        window.draw_ex(
+            &geom::Rectangle::new((system_dest.x - 40.0, system_dest.y + 14.0), (80.0 * bonus_fraction, 3.0)),
+            Background::Col(self.hud_color.with_alpha(bonus_alpha)),
+            geom::Transform::IDENTITY,
+            HUD_LAYER,
+        );
+
+        #[cfg(feature = "debug-tools")]
+        self.draw_inspector(window)?;
+
+        if self.transition_alpha > 0.0 {
             window.draw_ex(
-                &target_rect,
-                Background::Img(&i),
-                transform,
-                actor.layer,
+                &geom::Rectangle::new((0.0, 0.0), (self.screen_width, self.screen_height)),
+                Background::Col(Color::BLACK.with_alpha(self.transition_alpha)),
+                geom::Transform::IDENTITY,
+                TRANSITION_LAYER,
             );
-            Ok(())
-        })
+        }
+
+        if quit_fraction > 0.0 {
+            let bar = geom::Rectangle::new((self.screen_width / 2.0 - 100.0, self.screen_height / 2.0), (200.0, 12.0));
+            window.draw_ex(&bar, Background::Col(Color::WHITE.with_alpha(0.3)), geom::Transform::IDENTITY, TRANSITION_LAYER + 1);
+            let fill = geom::Rectangle::new(bar.pos, (bar.size.x * quit_fraction, bar.size.y));
+            window.draw_ex(&fill, Background::Col(Color::RED), geom::Transform::IDENTITY, TRANSITION_LAYER + 2);
+        }
+
+        // The hold to confirm an import, under the summary.
+        if let Some((_, import_fraction)) = save_lines.filter(|(_, fraction)| *fraction > 0.0) {
+            let bar = geom::Rectangle::new((self.screen_width / 2.0 - 100.0, self.screen_height / 2.0 + 60.0), (200.0, 12.0));
+            window.draw_ex(&bar, Background::Col(Color::WHITE.with_alpha(0.3)), geom::Transform::IDENTITY, HUD_LAYER + 1);
+            let fill = geom::Rectangle::new(bar.pos, (bar.size.x * import_fraction, bar.size.y));
+            window.draw_ex(&fill, Background::Col(self.hud_color), geom::Transform::IDENTITY, HUD_LAYER + 2);
+        }
+
+        Ok(())
     }
 }
 
-impl State for MainState {
-    fn new() -> quicksilver::Result<Self> {
-        MainState::new()
+pub fn main() -> quicksilver::Result<()> {
+    #[cfg(feature = "debug-tools")]
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if debug_tools::run_command(&args) {
+            return Ok(());
+        }
     }
-    
-    fn update(&mut self, _window: &mut Window) -> quicksilver::Result<()> {
-        const DESIRED_FPS: u32 = 60;
-        let seconds = 1.0 / (DESIRED_FPS as f32);
 
-        // Update the player state based on the user input.
-        player_handle_input(&mut self.player, &self.input, seconds);
-        self.player_shot_timeout -= seconds;
-        if self.input.fire && self.player_shot_timeout < 0.0 {
-            self.fire_player_shot();
+    let (width, height) = field_size();
+    run::<MainState>("Systems Critical", Vector::new(width, height),
+        Settings::default()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / DESIRED_FPS as f32;
+
+    /// A fresh game, run from an empty folder so nothing it
+    /// loads or saves touches the checkout.
+    /// A fresh game with default settings that never writes anything.
+    fn test_state() -> MainState {
+        MainState::with_saves(Tunables::default(), HighScores::default(), false).unwrap()
+    }
+
+    #[test]
+    fn fast_shots_keep_their_speed() {
+        let mut shot = create_shot();
+        shot.velocity = Vector2::new(0.0, FAST_SHOT_SPEED);
+        update_actor_position(&mut shot, 1.0 / DESIRED_FPS as f32);
+        assert_eq!(shot.velocity.len(), FAST_SHOT_SPEED);
+    }
+
+    #[test]
+    fn game_over_freezes_a_level_end() {
+        let mut s = test_state();
+        s.rocks.clear();
+        // The ship made it into the wormhole and died on the same frame.
+        s.wormhole.clear();
+        s.player.life = 0.0;
+        s.lives = 1;
+        s.step(DT);
+        assert!(s.state == GameState::GameOver);
+        assert!(s.pending_change.is_none());
+
+        let score = s.score;
+        for _ in 0..(2.0 * FADE_TIME / DT) as usize {
+            s.step(DT);
         }
-        self.player_radar_timeout -= seconds;
-        if self.input.radar && self.player_radar_timeout < 0.0 {
-            self.fire_player_radar();
+        assert_eq!(s.level, 0);
+        assert_eq!(s.score, score);
+        assert!(s.state == GameState::GameOver);
+    }
+
+    fn rock_at(x: f32, y: f32, size: f32) -> Actor {
+        let mut rock = create_rock();
+        rock.pos = Vector2::new(x, y);
+        rock.bbox_size = size;
+        rock
+    }
+
+    #[test]
+    fn line_of_sight_clear_and_blocked() {
+        let (a, b) = (Vector2::new(0.0, 0.0), Vector2::new(100.0, 0.0));
+        let clear = [rock_at(50.0, 50.0, 10.0), rock_at(150.0, 0.0, 10.0)];
+        assert!(line_of_sight(a, b, &clear, &[], 800.0, 600.0));
+        let blocked = [rock_at(50.0, 5.0, 10.0)];
+        assert!(!line_of_sight(a, b, &blocked, &[], 800.0, 600.0));
+        assert!(line_of_sight(a, b, &blocked, &[0], 800.0, 600.0));
+    }
+
+    #[test]
+    fn line_of_sight_across_the_seam() {
+        // The short way between these is across the edge, not through the middle.
+        let (a, b) = (Vector2::new(390.0, 0.0), Vector2::new(-390.0, 0.0));
+        assert!(line_of_sight(a, b, &[rock_at(0.0, 0.0, 10.0)], &[], 800.0, 600.0));
+        assert!(!line_of_sight(a, b, &[rock_at(-400.0, 0.0, 10.0)], &[], 800.0, 600.0));
+        assert!(!line_of_sight(a, b, &[rock_at(398.0, 2.0, 10.0)], &[], 800.0, 600.0));
+    }
+
+    #[test]
+    fn line_of_sight_tangent_is_clear() {
+        let (a, b) = (Vector2::new(0.0, 0.0), Vector2::new(100.0, 0.0));
+        assert!(line_of_sight(a, b, &[rock_at(50.0, 10.0, 10.0)], &[], 800.0, 600.0));
+        assert!(!line_of_sight(a, b, &[rock_at(50.0, 9.9, 10.0)], &[], 800.0, 600.0));
+    }
+
+    #[test]
+    fn grade_thresholds() {
+        let mut grades = GRADE_THRESHOLDS.iter().map(|(grade, _)| *grade).chain(Some('C'));
+        grades.next();
+        for ((grade, min), next) in GRADE_THRESHOLDS.iter().zip(grades) {
+            assert_eq!(grade_for_rating(*min), *grade);
+            assert_eq!(grade_for_rating(*min - 0.001), next);
         }
+        assert_eq!(level_grade(LEVEL_PAR_TIME, 1.0), 'S');
+        assert_eq!(level_grade(0.0, 1.0), 'S');
+        // Twice par with every shot hitting rates 0.7.
+        assert_eq!(level_grade(2.0 * LEVEL_PAR_TIME, 1.0), 'B');
+        assert_eq!(level_grade(10.0 * LEVEL_PAR_TIME, 0.0), 'C');
+    }
 
-        // Update the physics for all actors.
-        // First the player...
-        update_actor_position(&mut self.player, seconds);
-        wrap_actor_position(
-            &mut self.player,
-            self.screen_width as f32,
-            self.screen_height as f32,
+    fn docked_wormhole() -> Actor {
+        let mut wormhole = create_wormhole();
+        wormhole.pos = Vector2::new(100.0, 0.0);
+        wormhole.charge = 1.0;
+        wormhole
+    }
+
+    #[test]
+    fn docking_held_slow_gets_through() {
+        let wormhole = docked_wormhole();
+        let mut player = create_player();
+        player.pos = Vector2::new(95.0, 0.0);
+        player.velocity = Vector2::new(DOCKING_MAX_SPEED, 0.0);
+        let mut held = 0.0;
+        for _ in 0..(DOCKING_TIME / DT).ceil() as usize - 1 {
+            match docking_step(&player, &wormhole, held, DT, 800.0, 600.0) {
+                Docking::Holding(h) => held = h,
+                other => panic!("docked too soon: {:?}", other),
+            }
+        }
+        assert_eq!(docking_step(&player, &wormhole, held, DT, 800.0, 600.0), Docking::Docked);
+    }
+
+    #[test]
+    fn docking_too_fast_bounces() {
+        let wormhole = docked_wormhole();
+        let mut player = create_player();
+        player.pos = Vector2::new(95.0, 0.0);
+        player.velocity = Vector2::new(DOCKING_MAX_SPEED + 10.0, 0.0);
+        assert_eq!(
+            docking_step(&player, &wormhole, 0.2, DT, 800.0, 600.0),
+            Docking::TooFast(Some(Vector2::new(-2.0 * (DOCKING_MAX_SPEED + 10.0), 0.0)))
         );
+        // Already heading out, so it's left alone.
+        player.velocity = -player.velocity;
+        assert_eq!(docking_step(&player, &wormhole, 0.2, DT, 800.0, 600.0), Docking::TooFast(None));
+    }
 
-        // Then the shots...
-        for act in &mut self.shots {
-            update_actor_position(act, seconds);
-            wrap_actor_position(act, self.screen_width as f32, self.screen_height as f32);
-            handle_timed_life(act, seconds);
+    #[test]
+    fn docking_drifting_apart_starts_again() {
+        let wormhole = docked_wormhole();
+        let mut player = create_player();
+        player.pos = wormhole.pos + Vector2::new(0.0, PLAYER_BBOX + WORMHOLE_BBOX);
+        assert_eq!(docking_step(&player, &wormhole, 0.4, DT, 800.0, 600.0), Docking::Apart);
+        // Across the seam still counts as close.
+        let mut wormhole = docked_wormhole();
+        wormhole.pos = Vector2::new(398.0, 0.0);
+        player.pos = Vector2::new(-398.0, 0.0);
+        assert_eq!(docking_step(&player, &wormhole, 0.0, DT, 800.0, 600.0), Docking::Holding(DT));
+    }
+
+    #[test]
+    fn keys_let_go_while_unfocused_arent_stuck() {
+        let mut input = InputState::default();
+        input.set_key(Key::W, true);
+        input.set_key(Key::A, true);
+        input.set_key(Key::Up, true);
+        // The releases happen in another window and never arrive.
+        input.release_all();
+        let actions = Actions::derive(&input, &Systems::Wepons, true);
+        assert_eq!((actions.xaxis, actions.yaxis, actions.fire), (0.0, 0.0, false));
+        let actions = Actions::derive(&input, &Systems::Engines, true);
+        assert_eq!(actions.yaxis, 0.0);
+    }
+
+    #[test]
+    fn opposite_turn_keys_cancel_out() {
+        let mut input = InputState::default();
+        input.set_key(Key::A, true);
+        input.set_key(Key::Right, true);
+        assert_eq!(Actions::derive(&input, &Systems::Engines, false).xaxis, 0.0);
+        input.set_key(Key::Right, false);
+        assert_eq!(Actions::derive(&input, &Systems::Engines, false).xaxis, -1.0);
+    }
+
+    #[test]
+    fn every_turn_key_ordering() {
+        // Each press has to come before its own release.
+        let orders = [[0, 1, 2, 3], [0, 1, 3, 2], [0, 2, 1, 3], [1, 0, 2, 3], [1, 0, 3, 2], [1, 3, 0, 2]];
+        let turn_keys = [(Key::A, -1), (Key::Left, -1), (Key::D, 1), (Key::Right, 1)];
+        // Every pair, including both keys for the same way.
+        for (k, &first) in turn_keys.iter().enumerate() {
+            for &second in &turn_keys[k + 1..] {
+                // First down, second down, first up, second up.
+                let events = [(first, true), (second, true), (first, false), (second, false)];
+                for order in &orders {
+                    let mut input = InputState::default();
+                    let (mut first_held, mut second_held) = (false, false);
+                    for &i in order {
+                        let ((key, _), down) = events[i];
+                        input.set_key(key, down);
+                        if key == first.0 {
+                            first_held = down;
+                        } else {
+                            second_held = down;
+                        }
+                        let turning = |dir| (first_held && first.1 == dir) || (second_held && second.1 == dir);
+                        let expected = turning(1) as i32 as f32 - turning(-1) as i32 as f32;
+                        let xaxis = Actions::derive(&input, &Systems::Engines, false).xaxis;
+                        assert_eq!(xaxis, expected, "{:?} {:?} {:?}", first.0, second.0, order);
+                    }
+                }
+            }
         }
+    }
 
-        // And radar
-        for act in &mut self.radar {
-            handle_timed_life(act, seconds);
+    #[test]
+    fn held_w_follows_the_selected_system() {
+        let mut input = InputState::default();
+        input.set_key(Key::W, true);
+        let actions = Actions::derive(&input, &Systems::Wepons, false);
+        assert!(actions.fire && actions.yaxis == 0.0);
+        let actions = Actions::derive(&input, &Systems::Engines, false);
+        assert!(!actions.fire && actions.yaxis == 1.0);
+        let actions = Actions::derive(&input, &Systems::Radar, false);
+        assert!(actions.radar && !actions.fire);
+        input.set_key(Key::W, false);
+        let actions = Actions::derive(&input, &Systems::Engines, false);
+        assert_eq!(actions.yaxis, 0.0);
+    }
+
+    #[test]
+    fn orbits_dont_drift() {
+        let center = Vector2::new(120.0, -80.0);
+        let mut satellite = create_satellite();
+        update_orbit(&mut satellite, center, 0.0);
+        for _ in 0..10_000 {
+            update_orbit(&mut satellite, center, DT);
+            let radius = (satellite.pos - center).len();
+            assert!((radius - SATELLITE_ORBIT_RADIUS).abs() < 1e-3, "radius {}", radius);
+            assert!(satellite.facing >= 0.0 && satellite.facing < 2.0 * std::f32::consts::PI);
+        }
+    }
+
+    #[test]
+    fn wormhole_spot_rules() {
+        let (sx, sy) = (800.0, 600.0);
+        let player = Vector2::new(0.0, 0.0);
+        let rocks = [rock_at(-150.0, 0.0, 20.0)];
+        // The far corner, clear of everything.
+        assert!(wormhole_spot_score(Vector2::new(400.0, 300.0), player, &rocks, sx, sy) >= 1.0);
+        // Too close to the player.
+        assert!(wormhole_spot_score(Vector2::new(100.0, 0.0), player, &rocks, sx, sy) < 1.0);
+        // Far enough, but right on a rock across the seam.
+        let rocks = [rock_at(-390.0, -290.0, 20.0)];
+        assert!(wormhole_spot_score(Vector2::new(400.0, 300.0), player, &rocks, sx, sy) < 1.0);
+    }
+
+    #[test]
+    fn placed_wormholes_follow_the_rules() {
+        let (sx, sy) = (SCREEN_WIDTH, SCREEN_HEIGHT);
+        for level in 0..100 {
+            let player = Vector2::new((level * 37 % 800) as f32 - 400.0, (level * 53 % 600) as f32 - 300.0);
+            let rocks = create_rocks(level % 10 * 2 + 5, player, 100.0, 250.0);
+            let spot = place_wormhole(player, &rocks, sx, sy);
+            let distance = wrapped_offset(player, spot, sx, sy).len();
+            assert!(distance >= WORMHOLE_MIN_DISTANCE_FRACTION * max_wrapped_distance(sx, sy));
+            assert!(wormhole_clearance(spot, &rocks, sx, sy) >= WORMHOLE_ROCK_CLEARANCE);
         }
+    }
 
-        // And finally the rocks.
-        for act in &mut self.rocks {
-            update_actor_position(act, seconds);
-            wrap_actor_position(act, self.screen_width as f32, self.screen_height as f32);
+    #[test]
+    fn wormhole_placement_settles_for_the_best_spot() {
+        // Rocks everywhere, so nothing follows the rules.
+        let rocks = rocks_everywhere();
+        let spot = place_wormhole(Vector2::ZERO, &rocks, 800.0, 600.0);
+        assert!(spot.x.abs() <= 400.0 && spot.y.abs() <= 300.0);
+        assert!(wormhole_spot_score(spot, Vector2::ZERO, &rocks, 800.0, 600.0) < 1.0);
+    }
+
+    fn rocks_everywhere() -> Vec<Actor> {
+        let mut rocks = Vec::new();
+        for x in -8..8 {
+            for y in -6..6 {
+                rocks.push(rock_at(x as f32 * 50.0 + 25.0, y as f32 * 50.0 + 25.0, 30.0));
+            }
         }
+        rocks
+    }
 
-        // Handle the results of things moving:
-        // collision detection, object death, and if
-        // we have killed all the rocks in the level,
-        // spawn more of them.
-        self.handle_collisions();
+    /// A small rock that one shot destroys.
+    fn small_rock_at(x: f32, y: f32) -> Actor {
+        let mut rock = rock_at(x, y, 10.0);
+        rock.size = RockSize::Small;
+        rock.life = ROCK_SHOT_DAMAGE;
+        rock
+    }
 
-        self.clear_dead_stuff();
+    #[test]
+    fn piercing_shot_goes_through_aligned_rocks() {
+        let mut s = test_state();
+        // Out of the way, taking it away would end the level.
+        s.wormhole[0].pos = Vector2::new(-300.0, -250.0);
+        let rock_in_line = |x: f32, size: RockSize| {
+            let mut rock = create_rock();
+            rock.pos = Vector2::new(x, 200.0);
+            rock.size = size;
+            rock.bbox_size = size.bbox();
+            rock.life = rock_life(rock.bbox_size);
+            rock.velocity = Vector2::ZERO;
+            rock
+        };
+        // Two that break in one hit, then a whole rock that doesn't.
+        s.rocks = vec![
+            rock_in_line(100.0, RockSize::Medium),
+            rock_in_line(200.0, RockSize::Medium),
+            rock_in_line(300.0, RockSize::Large),
+        ];
+        let (first, second, last) = (s.rocks[0].id, s.rocks[1].id, s.rocks[2].id);
+        let mut shot = create_shot();
+        shot.pos = Vector2::new(40.0, 200.0);
+        shot.velocity = Vector2::new(SHOT_SPEED, 0.0);
+        shot.pierce_left = 2;
+        s.shots = vec![shot];
+        let rock = |s: &MainState, id| s.rocks.iter().find(|r| r.id == id).map(|r| r.life);
 
-        // self.check_for_level_respawn();
-        self.check_for_level_end();
-        // Finally we check for our end state.
-        // I want to have a nice death screen eventually,
-        // but for now we just quit.
-        if self.player.life <= 0.0 {
-            println!("Your score was {}", self.score);
-            println!("Your level was {}", self.level);
-            println!("Try Again");
-            MainState::reset(self);
+        // Past the first rock and its pieces, on the way to the second.
+        for _ in 0..30 {
+            s.step(DT);
         }
+        assert_eq!(rock(&s, first), None);
+        assert!(rock(&s, second).is_some());
+        assert_eq!(s.shots.len(), 1);
+        assert_eq!(s.shots[0].pierce_left, 1);
 
-        Ok(())
+        // The second breaks too, and the whole rock stops the shot.
+        for _ in 0..60 {
+            s.step(DT);
+        }
+        assert_eq!(rock(&s, second), None);
+        assert_eq!(s.rock_ledger.destroyed_by_shot, 2);
+        assert_eq!(rock(&s, last), Some(rock_life(ROCK_BBOX) - ROCK_SHOT_DAMAGE));
+        assert!(s.shots.is_empty());
     }
 
-    fn event(&mut self, event: &Event, _window: &mut Window) -> quicksilver::Result<()> {
-        match event {
-            // Buttons pressed
-            Event::Key(Key::Key1, ButtonState::Pressed) => {
-                self.player.sys = Systems::Engines;
-            }
-            Event::Key(Key::Key2, ButtonState::Pressed) => {
-                self.player.sys = Systems::Wepons;
-            }
-            Event::Key(Key::Key3, ButtonState::Pressed) => {
-                self.player.sys = Systems::Radar;
-            }
-            Event::Key(Key::W, ButtonState::Pressed) => {
-                if self.player.sys == Systems::Radar {
-                    self.input.radar = true;
-                } else if self.player.sys == Systems::Wepons {
-                    self.input.fire = true;
-                } else {
-                    self.input.yaxis = 1.0;
-                }
-            }
-            Event::Key(Key::A, ButtonState::Pressed) => {
-                self.input.xaxis = -1.0;
-            }
-            Event::Key(Key::D, ButtonState::Pressed) => {
-                self.input.xaxis = 1.0;
-            }
-            Event::Key(Key::Escape, ButtonState::Pressed) => {
-                std::process::exit(0);
+    #[test]
+    fn piercing_shot_goes_through_whole_rocks() {
+        let mut s = test_state();
+        let mut rock = create_rock();
+        rock.pos = Vector2::new(100.0, 0.0);
+        s.rocks = vec![rock];
+        let mut shot = create_shot();
+        shot.pos = Vector2::new(100.0, 0.0);
+        shot.pierce_left = 1;
+        s.shots = vec![shot];
+        // Still inside the rock on the next check, but it's only hit once.
+        s.handle_collisions();
+        s.handle_collisions();
+        assert_eq!(s.rocks[0].life, rock_life(ROCK_BBOX) - ROCK_SHOT_DAMAGE);
+        assert_eq!(s.shots[0].pierce_left, 0);
+        assert!(s.shots[0].life > 0.0);
+    }
+
+    // Long enough for one step's travel to be wider than a small rock.
+    const LONG_DT: f32 = 0.25;
+
+    /// A state with only a still small rock at `rock`, stepped once
+    /// by `LONG_DT` in `substeps` pieces after `setup`.
+    fn step_past_small_rock(substeps: u32, rock: Point2, setup: impl Fn(&mut MainState)) -> MainState {
+        let mut s = test_state();
+        s.tunables.physics_substeps = substeps;
+        // Out of the way, taking it away would end the level.
+        s.wormhole[0].pos = Vector2::new(-300.0, -250.0);
+        let mut small = small_rock_at(rock.x, rock.y);
+        small.bbox_size = RockSize::Small.bbox();
+        small.velocity = Vector2::ZERO;
+        s.rocks = vec![small];
+        setup(&mut s);
+        s.step(LONG_DT);
+        s
+    }
+
+    #[test]
+    fn fastest_shot_cant_skip_a_small_rock() {
+        let fire = |s: &mut MainState| {
+            let mut shot = create_shot();
+            shot.pos = Vector2::new(100.0, 100.0);
+            shot.velocity = Vector2::new(MAX_SHOT_PHYSICS_VEL, 0.0);
+            s.shots = vec![shot];
+        };
+        // One step carries the shot from one side of the rock to the other.
+        let rock = Vector2::new(140.0, 100.0);
+        assert!(MAX_SHOT_PHYSICS_VEL * LONG_DT > 2.0 * (SHOT_BBOX + RockSize::Small.bbox()));
+        let s = step_past_small_rock(1, rock, fire);
+        assert_eq!(s.rock_ledger.destroyed_by_shot, 0);
+        let s = step_past_small_rock(16, rock, fire);
+        assert_eq!(s.rock_ledger.destroyed_by_shot, 1);
+    }
+
+    #[test]
+    fn boosting_ship_cant_skip_a_small_rock() {
+        let boost = |s: &mut MainState| {
+            s.player.pos = Vector2::new(100.0, -100.0);
+            s.player.velocity = Vector2::new(MAX_PLAYER_PHYSICS_VEL, 0.0);
+        };
+        let rock = Vector2::new(127.0, -100.0);
+        let s = step_past_small_rock(1, rock, boost);
+        assert_eq!(s.lives, STARTING_LIVES);
+        let s = step_past_small_rock(8, rock, boost);
+        assert_eq!(s.lives, STARTING_LIVES - 1);
+    }
+
+    #[test]
+    fn belt_rocks_stay_in_the_spread() {
+        let (sx, sy) = (800.0, 600.0);
+        for &direction in &[1.0, -1.0] {
+            // Straddling the top and bottom edge.
+            let belt = Belt {
+                center_y: 290.0,
+                half_width: 60.0,
+                flow: Vector2::new(direction * 40.0, 0.0),
+                side: 1.0,
+                rocks: 200,
+            };
+            for rock in belt.create_rocks(sx, sy) {
+                // Measured from the flow, so heading left doesn't
+                // trip over the jump from pi to -pi.
+                let v = rock.velocity;
+                let off_flow = (belt.flow.x * v.y - belt.flow.y * v.x).atan2(belt.flow.dot(v));
+                assert!(off_flow.abs() <= BELT_SPREAD + 1e-4, "{} off the flow", off_flow);
+                assert!(rock.pos.y.abs() <= sy / 2.0);
+                let across = wrapped_offset(Vector2::new(rock.pos.x, belt.center_y), rock.pos, sx, sy).y;
+                assert!(across.abs() <= belt.half_width + 1e-3, "{} across the belt", across);
             }
-            // Buttons released
-            Event::Key(Key::W, ButtonState::Released) => {
-                self.input.yaxis = 0.0;
-                self.input.fire = false;
-                self.input.radar = false;
+        }
+    }
+
+    #[test]
+    fn holding_on_gives_one_ship_back_a_level() {
+        let mut s = test_state();
+        s.lives = 1;
+        let hold = (LAST_STAND_TIME / DT).ceil() as usize + 1;
+        for _ in 0..hold {
+            s.update_last_stand(DT);
+        }
+        assert_eq!(s.lives, 2);
+        // Off the last stand now, so the timer starts over.
+        s.update_last_stand(DT);
+        assert_eq!(s.last_stand_time, 0.0);
+
+        s.lives = 1;
+        for _ in 0..2 * hold {
+            s.update_last_stand(DT);
+        }
+        assert_eq!(s.lives, 1);
+
+        s.next_level();
+        for _ in 0..hold {
+            s.update_last_stand(DT);
+        }
+        assert_eq!(s.lives, 2);
+    }
+
+    #[test]
+    fn respawning_restarts_the_hold() {
+        let mut s = test_state();
+        s.lives = 1;
+        for _ in 0..60 {
+            s.update_last_stand(DT);
+        }
+        assert!(s.last_stand_time > 0.0);
+        s.respawn();
+        assert_eq!(s.last_stand_time, 0.0);
+    }
+
+    fn drop_waypoint_at(s: &mut MainState, x: f32, y: f32) {
+        s.player.pos = Vector2::new(x, y);
+        s.toggle_waypoint();
+    }
+
+    #[test]
+    fn waypoints_replace_the_oldest() {
+        let mut s = test_state();
+        for i in 0..MAX_WAYPOINTS + 1 {
+            drop_waypoint_at(&mut s, i as f32 * 100.0 - 200.0, 0.0);
+        }
+        assert_eq!(s.waypoints.len(), MAX_WAYPOINTS);
+        let expected: Vec<Point2> = (1..MAX_WAYPOINTS + 1).map(|i| Vector2::new(i as f32 * 100.0 - 200.0, 0.0)).collect();
+        assert_eq!(s.waypoints, expected);
+    }
+
+    #[test]
+    fn waypoints_pick_up_the_closest() {
+        let mut s = test_state();
+        drop_waypoint_at(&mut s, 0.0, 0.0);
+        drop_waypoint_at(&mut s, 100.0, 0.0);
+        drop_waypoint_at(&mut s, 140.0, 0.0);
+        assert_eq!(s.waypoints.len(), 3);
+        // Both of the last two are in reach, the closer one goes.
+        drop_waypoint_at(&mut s, 125.0, 0.0);
+        assert_eq!(s.waypoints, vec![Vector2::new(0.0, 0.0), Vector2::new(100.0, 0.0)]);
+        drop_waypoint_at(&mut s, 0.0, 10.0);
+        assert_eq!(s.waypoints, vec![Vector2::new(100.0, 0.0)]);
+
+        // Across the seam is in reach too.
+        drop_waypoint_at(&mut s, 395.0, 0.0);
+        drop_waypoint_at(&mut s, -395.0, 0.0);
+        assert_eq!(s.waypoints, vec![Vector2::new(100.0, 0.0)]);
+
+        s.next_level();
+        assert!(s.waypoints.is_empty());
+    }
+
+    #[test]
+    fn repulsor_cone_edges() {
+        let in_cone = |point| point_in_cone(Vector2::ZERO, 0.0, REPULSOR_HALF_ANGLE, REPULSOR_RANGE, point);
+        assert!(in_cone(Vector2::ZERO));
+        assert!(in_cone(Vector2::new(0.0, REPULSOR_RANGE)));
+        assert!(!in_cone(Vector2::new(0.0, REPULSOR_RANGE + 0.1)));
+        assert!(!in_cone(Vector2::new(0.0, -10.0)));
+        assert!(in_cone(vec_from_angle(REPULSOR_HALF_ANGLE - 0.01) * 100.0));
+        assert!(in_cone(vec_from_angle(-REPULSOR_HALF_ANGLE + 0.01) * 100.0));
+        assert!(!in_cone(vec_from_angle(REPULSOR_HALF_ANGLE + 0.01) * 100.0));
+        assert!(!in_cone(vec_from_angle(REPULSOR_HALF_ANGLE - 0.01) * (REPULSOR_RANGE + 1.0)));
+    }
+
+    #[test]
+    fn repulsed_rocks_are_held_to_max_speed() {
+        let mut s = test_state();
+        let mut close = rock_at(0.0, 20.0, 10.0);
+        close.velocity = Vector2::new(0.0, 40.0);
+        let far = rock_at(0.0, 140.0, 10.0);
+        let behind = rock_at(0.0, -20.0, 10.0);
+        s.rocks = vec![close, far, behind];
+        s.player.pos = Vector2::ZERO;
+        s.player.facing = 0.0;
+        s.fire_player_repulsor();
+        assert!((s.rocks[0].velocity.len() - MAX_ROCK_VEL).abs() < 1e-3);
+        // Near the end of the range the push is gentle enough to keep.
+        let strength = REPULSOR_IMPULSE * (1.0 - 140.0 / REPULSOR_RANGE);
+        assert!((s.rocks[1].velocity.y - strength).abs() < 1e-3);
+        assert_eq!(s.rocks[2].velocity, Vector2::ZERO);
+    }
+
+    #[test]
+    fn one_wormhole_one_level() {
+        for &docking in &[false, true] {
+            let mut s = test_state();
+            s.tunables.docking = docking;
+            s.rocks.clear();
+            s.wormhole[0].pos = s.player.pos;
+            s.wormhole[0].charge = 1.0;
+            // Long enough to dock, fade out, show the grade and fade back in.
+            let frames = ((DOCKING_TIME + 2.0 * FADE_TIME + LEVEL_COMPLETE_TIME) / DT) as usize + 30;
+            for _ in 0..frames {
+                s.step(DT);
+                assert!(s.level <= 1);
             }
-            Event::Key(Key::A, ButtonState::Released) => {
-                self.input.xaxis = 0.0;
+            assert_eq!(s.level, 1);
+            assert_eq!(s.level_grades.len(), 1);
+            assert!(!s.in_transition());
+        }
+    }
+
+    #[test]
+    fn each_kind_has_its_own_speed_limit() {
+        let kinds = vec![
+            (create_player(), MAX_PLAYER_PHYSICS_VEL),
+            (create_shot(), MAX_SHOT_PHYSICS_VEL),
+            (create_rock(), MAX_ROCK_PHYSICS_VEL),
+            (create_wormhole(), MAX_PHYSICS_VEL),
+        ];
+        for (mut actor, max_vel) in kinds {
+            actor.velocity = Vector2::new(600.0, 800.0);
+            update_actor_position(&mut actor, DT);
+            assert!((actor.velocity.len() - max_vel).abs() < 1e-3, "{:?}", actor.tag);
+            assert!((actor.velocity.normalize() - Vector2::new(0.6, 0.8)).len() < 1e-5);
+
+            actor.velocity = Vector2::new(0.0, max_vel - 1.0);
+            update_actor_position(&mut actor, DT);
+            assert_eq!(actor.velocity.len(), max_vel - 1.0);
+        }
+    }
+
+    #[test]
+    fn tiny_fields_dont_break_wrapping() {
+        assert_eq!(checked_field_size(0.0, -5.0), (MIN_FIELD_SIZE, MIN_FIELD_SIZE));
+        assert_eq!(checked_field_size(800.0, 1.0), (800.0, MIN_FIELD_SIZE));
+        for &(sx, sy) in &[(0.0, 0.0), (-10.0, 5.0), (1e-6, 1e-6), (1.0, 1.0), (f32::NAN, 1.0)] {
+            let mut rock = rock_at(1234.5, -987.0, 10.0);
+            rock.velocity = Vector2::new(80.0, -60.0);
+            for _ in 0..100 {
+                update_actor_position(&mut rock, DT);
+                wrap_actor_position(&mut rock, sx, sy);
+                assert!(rock.pos.x.is_finite() && rock.pos.y.is_finite(), "{} x {}", sx, sy);
             }
-            Event::Key(Key::D, ButtonState::Released) => {
-                self.input.xaxis = 0.0;
+            if sx > 0.0 && sy > 0.0 {
+                assert!(rock.pos.x.abs() <= sx / 2.0 && rock.pos.y.abs() <= sy / 2.0, "{} x {}", sx, sy);
             }
-            _ => (), // Do nothing
         }
-        Ok(())
     }
 
-    fn draw(&mut self, window: &mut Window) -> quicksilver::Result<()> {
-        // Clear the screen...
-        window.clear(Color::BLACK)?;
+    #[test]
+    fn paused_steps_one_at_a_time() {
+        let mut s = test_state();
+        s.rocks.clear();
+        s.player.velocity = Vector2::new(30.0, 0.0);
+        s.step(DT);
+        s.count_render_frame();
+        let (steps, frames, pos) = (s.sim_steps, s.render_frames, s.player.pos);
 
-        // Loop over all objects drawing them...
-        {
-            let assets = &mut self.assets;
-            let coords = (self.screen_width, self.screen_height);
+        s.debug_paused = true;
+        for _ in 0..5 {
+            s.step(DT);
+            s.count_render_frame();
+        }
+        assert_eq!((s.sim_steps, s.render_frames), (steps, frames));
+        assert_eq!(s.player.pos, pos);
 
-            let p = &self.player;
-            draw_actor(assets, window, p, coords)?;
+        // `.` draws one more frame without moving anything.
+        s.debug_frame_step = true;
+        s.step(DT);
+        s.count_render_frame();
+        s.count_render_frame();
+        assert_eq!((s.sim_steps, s.render_frames), (steps, frames + 1));
+        assert_eq!(s.player.pos, pos);
 
-            for s in &self.shots {
-                draw_actor(assets, window, s, coords)?;
-            }
+        // `,` runs exactly one update.
+        s.debug_step = true;
+        s.step(DT);
+        s.step(DT);
+        assert_eq!(s.sim_steps, steps + 1);
+        assert!((s.player.pos.x - (pos.x + 30.0 * DT)).abs() < 1e-4);
+    }
 
-            for r in &self.rocks {
-                draw_actor(assets, window, r, coords)?;
-            }
+    #[test]
+    fn threats_in_the_fog_stay_hidden() {
+        let mut s = test_state();
+        s.mutators.fog = true;
+        s.player.pos = Vector2::ZERO;
+        s.rocks = vec![rock_at(0.0, FOG_RADIUS + 50.0, 10.0)];
+        s.satellites = Vec::new();
+        s.update_fog();
+        assert!(s.nearest_threat().is_none());
 
-            for r in &self.radar {
-                draw_actor(assets, window, r, coords)?;
-            }
+        // Once radar has seen it, it shows up until it fades.
+        s.rocks[0].last_seen = s.run_stats.time;
+        assert_eq!(s.nearest_threat().map(|(offset, _)| offset), Some(Vector2::new(0.0, FOG_RADIUS + 50.0)));
+        s.run_stats.time += FOG_FADE_TIME;
+        assert!(s.nearest_threat().is_none());
 
-            for w in &self.wormhole {
-                draw_actor(assets, window, w, coords)?;
-            }
+        s.mutators.fog = false;
+        assert!(s.nearest_threat().is_some());
+    }
+
+    #[test]
+    fn radar_sees_across_the_seam() {
+        let mut s = test_state();
+        s.mutators.fog = true;
+        s.player.pos = Vector2::ZERO;
+        let mut radar = create_radar(0);
+        radar.pos = Vector2::new(390.0, 0.0);
+        radar.charge = 1.0;
+        radar.life = RADAR_LIFE - 0.5;
+        // 80 out, 30 of it past the right edge.
+        assert_eq!(radar_radius(&radar), 80.0);
+        s.radar = vec![radar];
+        s.rocks = vec![rock_at(-380.0, 0.0, 10.0), rock_at(300.0, 0.0, 10.0), rock_at(-300.0, 0.0, 10.0)];
+        s.run_stats.time = 5.0;
+        s.update_fog();
+        let seen: Vec<bool> = s.rocks.iter().map(|r| r.last_seen == 5.0).collect();
+        assert_eq!(seen, vec![true, false, false]);
+    }
+
+    #[test]
+    fn ledgers_add_up() {
+        let first = RockLedger {
+            destroyed_by_shot: 3,
+            destroyed_by_ram: 1,
+            fragments: 4,
+            ..RockLedger::new(5)
+        };
+        let second = RockLedger {
+            spawned_later: 2,
+            destroyed_by_blast: 2,
+            peak: 9,
+            ..RockLedger::new(7)
+        };
+        let total = RockLedger::total([first, second].iter());
+        assert_eq!((total.spawned_initial, total.spawned_later, total.fragments), (12, 2, 4));
+        assert_eq!((total.destroyed_by_shot, total.destroyed_by_blast, total.destroyed_by_ram), (3, 2, 1));
+        assert_eq!(total.destroyed(), 6);
+        assert_eq!(total.peak, 9);
+    }
+
+    #[test]
+    fn rammed_rocks_are_counted() {
+        let mut s = test_state();
+        s.player.pos = Vector2::ZERO;
+        s.rocks = vec![small_rock_at(5.0, 0.0)];
+        s.ram_timer = 1.0;
+        s.handle_collisions();
+        assert_eq!(s.rock_ledger.destroyed_by_ram, 1);
+        assert_eq!(s.rock_ledger.destroyed(), 1);
+        assert!(s.player.life > 0.0);
+    }
+
+    fn ticker_text(ticker: &Ticker) -> Vec<&str> {
+        ticker.entries.iter().map(|(entry, _)| entry.as_str()).collect()
+    }
+
+    #[test]
+    fn ticker_drops_the_oldest() {
+        let mut ticker = Ticker::default();
+        for i in 0..TICKER_ROWS + 2 {
+            ticker.push(format!("entry {}", i));
         }
+        let expected: Vec<String> = (2..TICKER_ROWS + 2).map(|i| format!("entry {}", i)).collect();
+        assert_eq!(ticker_text(&ticker), expected.iter().map(|e| e.as_str()).collect::<Vec<_>>());
+    }
 
-        // And draw the GUI elements in the right places.
-        let level_dest = Point2::new(100.0, 10.0);
-        let score_dest = Point2::new(300.0, 10.0);
+    #[test]
+    fn ticker_entries_expire_in_order() {
+        let mut ticker = Ticker::default();
+        ticker.push("first".to_string());
+        ticker.update(1.0);
+        ticker.push("second".to_string());
+        ticker.update(TICKER_TIME - 1.5);
+        assert_eq!(ticker_text(&ticker), vec!["first", "second"]);
+        // The first has been up for its whole time now.
+        ticker.update(0.5);
+        assert_eq!(ticker_text(&ticker), vec!["second"]);
+        ticker.update(0.5);
+        assert_eq!(ticker_text(&ticker), vec!["second"]);
+        ticker.update(0.5);
+        assert!(ticker.entries.is_empty());
+    }
 
-        let level_str = format!("Level: {}", self.level);
-        let score_str = format!("Score: {}", self.score);
+    #[test]
+    fn warps_send_the_ship_to_their_pair() {
+        let mut s = test_state();
+        s.rocks.clear();
+        s.warps = vec![create_warp(), create_warp()];
+        s.warps[0].pos = Vector2::new(-200.0, 0.0);
+        s.warps[1].pos = Vector2::new(200.0, 0.0);
+        for warp in &mut s.warps {
+            warp.charge = 1.0;
+        }
+        s.player.pos = s.warps[0].pos;
+        s.player.velocity = Vector2::new(0.0, 50.0);
+        s.handle_warps();
+        // Out of the far end, still heading up and clear of the exit.
+        let out = s.player.pos - s.warps[1].pos;
+        assert_eq!(out.x, 0.0);
+        assert!(out.y >= s.player.bbox_size + WORMHOLE_BBOX - 0.001);
+        assert_eq!(s.player.velocity, Vector2::new(0.0, 50.0));
+        assert!(s.warps.iter().all(|w| w.charge == 0.0));
 
-        self.assets.font.execute(|f| {
-            let style = FontStyle::new(24.0, Color::WHITE);
-            let text = f.render(&level_str, &style)?;
-            window.draw(&text.area().with_center(level_dest), Background::Img(&text));
+        // Straight back in goes nowhere until both ends charge up.
+        s.player.pos = s.warps[1].pos;
+        s.handle_warps();
+        assert_eq!(s.player.pos, s.warps[1].pos);
+        for warp in &mut s.warps {
+            handle_wormhole_charge(warp, WORMHOLE_CHARGE_TIME);
+        }
+        s.handle_warps();
+        assert!((s.player.pos - s.warps[0].pos).len() < s.player.bbox_size + WORMHOLE_BBOX + 0.001);
+        assert!(s.player.pos != s.warps[0].pos);
+    }
 
-            let text = f.render(&score_str, &style)?;
-            window.draw(&text.area().with_center(score_dest), Background::Img(&text));
+    fn armed_shot_at(pos: Point2) -> Actor {
+        let mut shot = create_shot();
+        shot.pos = pos;
+        shot.armed = true;
+        shot.arming = SHOT_ARMING_TIME;
+        shot.life = ARMED_SHOT_LIFE;
+        shot
+    }
 
-            Ok(())
-        })?;
+    #[test]
+    fn point_blank_shots_wait_to_arm() {
+        let mut s = test_state();
+        let player = s.player.pos;
+        s.shots = vec![armed_shot_at(player + Vector2::new(0.0, 10.0))];
+        s.detonate_shots();
+        assert!(s.blasts.is_empty());
+        assert!(s.shots[0].life > 0.0 && s.shots[0].armed);
 
-        Ok(())
+        // Once it has been out long enough it goes off where it is.
+        handle_shot_arming(&mut s.shots[0], SHOT_ARMING_TIME);
+        s.detonate_shots();
+        assert_eq!(s.blasts.len(), 1);
+        assert_eq!(s.shots[0].life, 0.0);
     }
-}
 
-pub fn main() -> quicksilver::Result<()> {
-    run::<MainState>("Systems Critical", Vector::new(800, 600),
-        Settings::default()
-    );
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn shots_clear_of_the_ship_go_off_straight_away() {
+        let mut s = test_state();
+        let player = s.player.pos;
+        s.shots = vec![
+            armed_shot_at(player + Vector2::new(0.0, 10.0)),
+            armed_shot_at(player + Vector2::new(SHOT_ARMING_RADIUS, 0.0)),
+        ];
+        s.detonate_shots();
+        assert_eq!(s.blasts.len(), 1);
+        assert_eq!(s.blasts[0].pos, s.shots[1].pos);
+        assert!(s.shots[0].life > 0.0);
+    }
+}
@@ -0,0 +1,69 @@
+//! Which file each asset is loaded from. `assets.ron` can point any
+//! of them at a different file, anything it leaves out keeps its
+//! default.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+const MANIFEST_FILE: &str = "assets.ron";
+
+/// Every asset the game loads, by name, and its default file.
+const DEFAULT_FILES: [(&str, &str); 7] = [
+    ("player_image", "player.png"),
+    ("shot_image", "shot.png"),
+    ("rock_image", "astroid.png"),
+    ("font", "DejaVuSerif.ttf"),
+    ("shot_sound", "pew.ogg"),
+    ("hit_sound", "boom.ogg"),
+    ("repulse_sound", "repulse.wav"),
+];
+
+/// The built in file for the asset called `name`.
+pub fn default_file(name: &str) -> Option<&'static str> {
+    DEFAULT_FILES.iter().find(|(n, _)| *n == name).map(|(_, file)| *file)
+}
+
+#[derive(Debug)]
+pub struct AssetManifest {
+    files: BTreeMap<String, String>,
+}
+
+impl AssetManifest {
+    /// Loads `assets.ron` over the defaults. Entries for assets that
+    /// don't exist or with no file name are skipped with a warning.
+    pub fn load() -> AssetManifest {
+        let mut files: BTreeMap<String, String> = DEFAULT_FILES
+            .iter()
+            .map(|(name, file)| (name.to_string(), file.to_string()))
+            .collect();
+
+        let overrides: BTreeMap<String, String> = match fs::read_to_string(MANIFEST_FILE) {
+            Ok(text) => ron::de::from_str(&text).unwrap_or_else(|e| {
+                println!("Ignoring {}: {}", MANIFEST_FILE, e);
+                BTreeMap::new()
+            }),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                println!("Can't read {}: {}", MANIFEST_FILE, e);
+                BTreeMap::new()
+            }
+        };
+        for (name, file) in overrides {
+            if default_file(&name).is_none() {
+                println!("{}: there's no asset called '{}'", MANIFEST_FILE, name);
+            } else if file.trim().is_empty() {
+                println!("{}: no file given for '{}'", MANIFEST_FILE, name);
+            } else {
+                files.insert(name, file);
+            }
+        }
+
+        AssetManifest { files }
+    }
+
+    /// The file to load the asset called `name` from.
+    pub fn file(&self, name: &str) -> &str {
+        &self.files[name]
+    }
+}
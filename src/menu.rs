@@ -0,0 +1,129 @@
+//! Pieces shared by anything the player picks options from.
+
+/// Seconds a destructive action's button has to be held down.
+pub const CONFIRM_HOLD_TIME: f32 = 0.8;
+
+/// Guards an action that can't be undone: it only happens once the
+/// button has been held the whole way, and letting go starts over.
+#[derive(Debug)]
+pub struct HoldToConfirm {
+    held: bool,
+    progress: f32,
+}
+
+impl HoldToConfirm {
+    pub fn new() -> HoldToConfirm {
+        HoldToConfirm {
+            held: false,
+            progress: 0.0,
+        }
+    }
+
+    pub fn press(&mut self) {
+        self.held = true;
+    }
+
+    pub fn release(&mut self) {
+        self.held = false;
+        self.progress = 0.0;
+    }
+
+    /// Advances the hold, returning true on the one update it completes.
+    /// The button has to be released and pressed again to go again.
+    pub fn update(&mut self, dt: f32) -> bool {
+        if !self.held {
+            return false;
+        }
+        self.progress += dt;
+        if self.progress >= CONFIRM_HOLD_TIME {
+            self.release();
+            return true;
+        }
+        false
+    }
+
+    /// How full the bar is, 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        (self.progress / CONFIRM_HOLD_TIME).min(1.0)
+    }
+}
+
+/// A list of `len` choices with one highlighted, moved through with
+/// the arrow keys and wrapping round at either end.
+#[derive(Debug)]
+pub struct Picker {
+    len: usize,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(len: usize, selected: usize) -> Picker {
+        Picker {
+            len,
+            selected: selected.min(len.saturating_sub(1)),
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn next(&mut self) {
+        if self.len > 0 {
+            self.selected = (self.selected + 1) % self.len;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if self.len > 0 {
+            self.selected = (self.selected + self.len - 1) % self.len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_once_when_held_the_whole_way() {
+        let mut hold = HoldToConfirm::new();
+        hold.press();
+        assert!(!hold.update(CONFIRM_HOLD_TIME / 2.0));
+        assert_eq!(hold.fraction(), 0.5);
+        assert!(hold.update(CONFIRM_HOLD_TIME / 2.0));
+        // Still held down, but it needs a fresh press to go again.
+        for _ in 0..100 {
+            assert!(!hold.update(CONFIRM_HOLD_TIME / 2.0));
+        }
+        assert_eq!(hold.fraction(), 0.0);
+    }
+
+    #[test]
+    fn letting_go_starts_over() {
+        let mut hold = HoldToConfirm::new();
+        hold.press();
+        assert!(!hold.update(CONFIRM_HOLD_TIME * 0.9));
+        hold.release();
+        assert_eq!(hold.fraction(), 0.0);
+        hold.press();
+        assert!(!hold.update(CONFIRM_HOLD_TIME / 2.0));
+        assert!(hold.update(CONFIRM_HOLD_TIME / 2.0));
+    }
+
+    #[test]
+    fn picker_wraps_round() {
+        let mut picker = Picker::new(3, 1);
+        picker.next();
+        picker.next();
+        assert_eq!(picker.selected(), 0);
+        picker.previous();
+        assert_eq!(picker.selected(), 2);
+        assert_eq!(Picker::new(3, 7).selected(), 2);
+
+        let mut empty = Picker::new(0, 0);
+        empty.next();
+        empty.previous();
+        assert_eq!(empty.selected(), 0);
+    }
+}
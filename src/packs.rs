@@ -0,0 +1,125 @@
+//! Asset packs let players drop replacement art and sounds into
+//! `static/packs/<pack>/` without recompiling. Any file a pack
+//! doesn't provide falls back to the default asset of the same name.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// The directory quicksilver loads assets from.
+const STATIC_DIR: &str = "static";
+/// Where packs live, relative to `STATIC_DIR`.
+const PACKS_DIR: &str = "packs";
+/// Optional metadata file inside a pack folder.
+const PACK_META: &str = "pack.ron";
+
+/// The contents of a `pack.ron`, both fields are optional.
+#[derive(Debug, Default, Deserialize)]
+struct PackMeta {
+    name: Option<String>,
+    author: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetPack {
+    /// Folder under `static/packs`, `None` for the built in assets.
+    dir: Option<String>,
+    pub name: String,
+    pub author: Option<String>,
+}
+
+impl AssetPack {
+    pub fn default_pack() -> AssetPack {
+        AssetPack {
+            dir: None,
+            name: "Default".to_string(),
+            author: None,
+        }
+    }
+
+    fn from_dir(dir: String) -> AssetPack {
+        let meta_path = Path::new(STATIC_DIR).join(PACKS_DIR).join(&dir).join(PACK_META);
+        let meta = match fs::read_to_string(&meta_path) {
+            Ok(text) => ron::de::from_str(&text).unwrap_or_else(|e| {
+                println!("Asset pack '{}': bad {}: {}", dir, PACK_META, e);
+                PackMeta::default()
+            }),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => PackMeta::default(),
+            Err(e) => {
+                println!("Asset pack '{}': can't read {}: {}", dir, PACK_META, e);
+                PackMeta::default()
+            }
+        };
+        AssetPack {
+            name: meta.name.unwrap_or_else(|| dir.clone()),
+            author: meta.author,
+            dir: Some(dir),
+        }
+    }
+
+    /// Resolves `file` through this pack, falling back to the file
+    /// outside any pack. Returns `None` when neither is usable.
+    pub fn resolve(&self, file: &str) -> Option<String> {
+        if let Some(dir) = &self.dir {
+            let path = format!("{}/{}/{}", PACKS_DIR, dir, file);
+            match check_file(&path) {
+                Ok(true) => return Some(path),
+                Ok(false) => (),
+                Err(e) => println!("Asset pack '{}': ignoring {}: {}", self.name, path, e),
+            }
+        }
+        match check_file(file) {
+            Ok(true) => Some(file.to_string()),
+            Ok(false) => {
+                println!("Missing asset {}", file);
+                None
+            }
+            Err(e) => {
+                println!("Bad asset {}: {}", file, e);
+                None
+            }
+        }
+    }
+}
+
+/// `Ok(false)` if the file simply isn't there, `Err` with the reason if
+/// it is there but can't be used.
+fn check_file(path: &str) -> Result<bool, String> {
+    match fs::metadata(Path::new(STATIC_DIR).join(path)) {
+        Ok(ref m) if !m.is_file() => Err("not a file".to_string()),
+        Ok(ref m) if m.len() == 0 => Err("file is empty".to_string()),
+        Ok(_) => Ok(true),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Lists the default pack followed by every pack folder found,
+/// sorted by folder name.
+pub fn discover_packs() -> Vec<AssetPack> {
+    let mut packs = vec![AssetPack::default_pack()];
+    let entries = match fs::read_dir(Path::new(STATIC_DIR).join(PACKS_DIR)) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return packs,
+        Err(e) => {
+            println!("Can't read the asset pack folder: {}", e);
+            return packs;
+        }
+    };
+    let mut dirs: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| match entry.file_name().into_string() {
+            Ok(name) => Some(name),
+            Err(name) => {
+                println!("Skipping asset pack with a non UTF-8 name: {:?}", name);
+                None
+            }
+        })
+        .collect();
+    dirs.sort();
+    packs.extend(dirs.into_iter().map(AssetPack::from_dir));
+    packs
+}
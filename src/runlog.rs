@@ -0,0 +1,210 @@
+//! A history of every run, one line each in `runs.csv`, for looking
+//! back over how games have gone and checking the balance.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const RUN_LOG_FILE: &str = "runs.csv";
+// Where a log written with different columns is moved to.
+const OLD_RUN_LOG_FILE: &str = "runs.old.csv";
+const HEADER: &str = "date,mode,score,level,rocks_destroyed,accuracy,duration,grades,wormhole_distances,\
+                      rocks_initial,rocks_later,rock_fragments,rocks_shot,rocks_blasted,rocks_rammed,peak_rocks";
+
+/// How a finished run went.
+#[derive(Debug)]
+pub struct RunRecord<'a> {
+    pub mode: &'a str,
+    pub score: i32,
+    pub level: i32,
+    pub rocks_destroyed: u32,
+    // Fraction of shots fired that hit something.
+    pub accuracy: f32,
+    // Seconds of play.
+    pub duration: f32,
+    // One letter for each level got through, in order.
+    pub grades: &'a str,
+    // How far each level's wormhole started from the player.
+    pub wormhole_distances: &'a [f32],
+    // The rock ledgers of every level added up.
+    pub rocks_initial: u32,
+    pub rocks_later: u32,
+    pub rock_fragments: u32,
+    pub rocks_shot: u32,
+    pub rocks_blasted: u32,
+    pub rocks_rammed: u32,
+    // The most rocks alive at once on any level.
+    pub peak_rocks: usize,
+}
+
+/// Adds a line for `record` to the log, starting the file with
+/// a header if it's new. Problems are reported, never fatal.
+pub fn append_run(record: &RunRecord) {
+    if let Err(e) = try_append(record) {
+        println!("Can't write to {}: {}", RUN_LOG_FILE, e);
+    }
+}
+
+fn try_append(record: &RunRecord) -> io::Result<()> {
+    retire_old_log()?;
+    let is_new = !Path::new(RUN_LOG_FILE).exists() || fs::metadata(RUN_LOG_FILE)?.len() == 0;
+    let mut file = OpenOptions::new().create(true).append(true).open(RUN_LOG_FILE)?;
+    if is_new {
+        writeln!(file, "{}", HEADER)?;
+    }
+    writeln!(file, "{}", record_line(record))
+}
+
+/// The line in the log for `record`, matching `HEADER`.
+fn record_line(record: &RunRecord) -> String {
+    format!(
+        "{},{},{},{},{},{:.2},{:.1},{},{},{},{},{},{},{},{},{}",
+        utc_now(),
+        record.mode,
+        record.score,
+        record.level,
+        record.rocks_destroyed,
+        record.accuracy,
+        record.duration,
+        record.grades,
+        // Split with `;` to keep it in one column.
+        record.wormhole_distances.iter().map(|d| format!("{:.0}", d)).collect::<Vec<_>>().join(";"),
+        record.rocks_initial,
+        record.rocks_later,
+        record.rock_fragments,
+        record.rocks_shot,
+        record.rocks_blasted,
+        record.rocks_rammed,
+        record.peak_rocks,
+    )
+}
+
+/// Both logs' runs in one, in the order they were played, and how
+/// many of them only `theirs` had. Each run is only kept once, however
+/// many times it's in the logs. Logs with other columns can't be put
+/// together.
+pub fn merge_logs(ours: Option<&str>, theirs: &str) -> Result<(String, usize), String> {
+    let mut runs = log_runs(ours.unwrap_or(""))?;
+    let kept = runs.len();
+    runs.extend(log_runs(theirs)?);
+    // Every line starts with its date.
+    runs.sort();
+    runs.dedup();
+    let added = runs.len() - kept;
+    let mut merged = String::from(HEADER);
+    for run in runs {
+        merged.push('\n');
+        merged.push_str(run);
+    }
+    merged.push('\n');
+    Ok((merged, added))
+}
+
+/// The runs in a log, each once.
+fn log_runs(log: &str) -> Result<Vec<&str>, String> {
+    let mut lines = log.lines();
+    let mut runs: Vec<&str> = match lines.next() {
+        Some(header) if header == HEADER => lines.filter(|line| !line.is_empty()).collect(),
+        None => Vec::new(),
+        Some(_) => return Err("the run history has different columns".to_string()),
+    };
+    runs.sort();
+    runs.dedup();
+    Ok(runs)
+}
+
+/// Moves a log from an older version with different columns out of
+/// the way, so every line in the file matches its header.
+fn retire_old_log() -> io::Result<()> {
+    let file = match fs::File::open(RUN_LOG_FILE) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut header = String::new();
+    BufReader::new(file).read_line(&mut header)?;
+    if !header.is_empty() && header.trim_end() != HEADER {
+        println!("{} has old columns, moving it to {}", RUN_LOG_FILE, OLD_RUN_LOG_FILE);
+        fs::rename(RUN_LOG_FILE, OLD_RUN_LOG_FILE)?;
+    }
+    Ok(())
+}
+
+/// The current time as `YYYY-MM-DD HH:MM:SS` in UTC.
+pub fn utc_now() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Days since 1970 to a calendar date, from Howard Hinnant's
+    // `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_matches_the_header() {
+        let record = RunRecord {
+            mode: "campaign",
+            score: 42,
+            level: 3,
+            rocks_destroyed: 12,
+            accuracy: 0.5,
+            duration: 61.25,
+            grades: "SAB",
+            wormhole_distances: &[410.0, 455.4],
+            rocks_initial: 15,
+            rocks_later: 2,
+            rock_fragments: 6,
+            rocks_shot: 9,
+            rocks_blasted: 2,
+            rocks_rammed: 1,
+            peak_rocks: 11,
+        };
+        let line = record_line(&record);
+        assert_eq!(line.split(',').count(), HEADER.split(',').count());
+        assert!(line.ends_with(",campaign,42,3,12,0.50,61.2,SAB,410;455,15,2,6,9,2,1,11"), "{}", line);
+    }
+
+    #[test]
+    fn merged_logs_keep_every_run_once() {
+        let ours = format!("{}\n2024-01-02 a\n2024-01-04 b\n", HEADER);
+        let theirs = format!("{}\n2024-01-01 c\n2024-01-04 b\n", HEADER);
+        let (merged, added) = merge_logs(Some(&ours), &theirs).unwrap();
+        assert_eq!(merged, format!("{}\n2024-01-01 c\n2024-01-02 a\n2024-01-04 b\n", HEADER));
+        assert_eq!(added, 1);
+        assert_eq!(merge_logs(None, &theirs).unwrap(), merge_logs(Some(""), &theirs).unwrap());
+        assert!(merge_logs(Some(&ours), "date,mode,score\n2024-01-01,campaign,3\n").is_err());
+    }
+
+    #[test]
+    fn untidy_logs_still_count_what_was_added() {
+        // Blank lines and the same run twice in the log already here.
+        let ours = format!("{}\n2024-01-02 a\n\n2024-01-02 a\n\n", HEADER);
+        let theirs = format!("{}\n2024-01-02 a\n", HEADER);
+        let (merged, added) = merge_logs(Some(&ours), &theirs).unwrap();
+        assert_eq!(merged, format!("{}\n2024-01-02 a\n", HEADER));
+        assert_eq!(added, 0);
+    }
+}
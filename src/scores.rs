@@ -0,0 +1,327 @@
+//! High scores, the best few runs kept separately for every mode and
+//! difficulty so an easy mode's scores can't crowd out a hard one's.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Where the scores are saved, relative to the working directory.
+pub const SCORES_FILE: &str = "highscores.ron";
+/// Written first and then renamed over `SCORES_FILE`, so a crash
+/// partway through saving leaves the old scores as they were.
+const SCORES_TEMP_FILE: &str = "highscores.ron.tmp";
+// Bumped whenever the saved layout changes.
+const SCORES_VERSION: u32 = 1;
+/// How many runs each board keeps.
+pub const BOARD_SIZE: usize = 10;
+
+/// How hard a run was, going by how much its mutators scale the score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Difficulty {
+    // Mutators that make things easier than they take away.
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn from_multiplier(multiplier: f32) -> Difficulty {
+        if multiplier < 1.0 {
+            Difficulty::Easy
+        } else if multiplier > 1.0 {
+            Difficulty::Hard
+        } else {
+            Difficulty::Normal
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+}
+
+/// One run on a board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub score: i32,
+    pub level: i32,
+    // Seconds of play.
+    pub time: f32,
+    // When the run started, which also tells a run still going apart
+    // from the others. Empty for scores from before there were boards.
+    pub started: String,
+    // Names of the mutators that were on.
+    pub mutators: Vec<String>,
+}
+
+impl ScoreEntry {
+    /// An entry with only a score, all the old save files had.
+    fn score_only(score: i32) -> ScoreEntry {
+        ScoreEntry {
+            score,
+            level: 0,
+            time: 0.0,
+            started: String::new(),
+            mutators: Vec::new(),
+        }
+    }
+}
+
+/// The layout of `SCORES_FILE`.
+#[derive(Serialize, Deserialize)]
+struct SavedScores {
+    version: u32,
+    boards: BTreeMap<(String, Difficulty), Vec<ScoreEntry>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HighScores {
+    // Best first.
+    boards: BTreeMap<(String, Difficulty), Vec<ScoreEntry>>,
+}
+
+impl HighScores {
+    /// Loads the saved scores. A missing or unreadable file just
+    /// means starting from nothing.
+    pub fn load() -> HighScores {
+        HighScores::load_from(Path::new(SCORES_FILE))
+    }
+
+    fn load_from(file: &Path) -> HighScores {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return HighScores::default(),
+            Err(e) => {
+                println!("Can't read {}: {}", file.display(), e);
+                return HighScores::default();
+            }
+        };
+        match HighScores::parse(&text) {
+            Ok(scores) => scores,
+            Err(e) => {
+                println!("Ignoring unreadable high scores in {}: {}", file.display(), e);
+                HighScores::default()
+            }
+        }
+    }
+
+    /// Reads either layout. The old one only had the best score for
+    /// each mode, which goes on the mode's normal board.
+    pub fn parse(text: &str) -> Result<HighScores, String> {
+        let saved: SavedScores = match ron::de::from_str(text) {
+            Ok(saved) => saved,
+            Err(e) => {
+                let by_mode: BTreeMap<String, i32> = ron::de::from_str(text).map_err(|_| e.to_string())?;
+                let boards = by_mode
+                    .into_iter()
+                    .map(|(mode, score)| ((mode, Difficulty::Normal), vec![ScoreEntry::score_only(score)]))
+                    .collect();
+                return Ok(HighScores { boards });
+            }
+        };
+        if saved.version != SCORES_VERSION {
+            return Err(format!("unknown version {}", saved.version));
+        }
+        Ok(HighScores { boards: saved.boards })
+    }
+
+    pub fn best(&self, mode: &str, difficulty: Difficulty) -> i32 {
+        self.board(mode, difficulty).first().map_or(0, |entry| entry.score)
+    }
+
+    /// The runs on one board, best first.
+    pub fn board(&self, mode: &str, difficulty: Difficulty) -> &[ScoreEntry] {
+        self.boards
+            .get(&(mode.to_string(), difficulty))
+            .map_or(&[], |entries| entries.as_slice())
+    }
+
+    /// Every mode with something on one of its boards.
+    pub fn modes(&self) -> Vec<&str> {
+        let mut modes: Vec<&str> = self.boards.keys().map(|(mode, _)| mode.as_str()).collect();
+        modes.dedup();
+        modes
+    }
+
+    /// Records a run on its board, returning where it ranks there or
+    /// `None` if it didn't make it. Submitting a run again as it goes
+    /// on updates its entry instead of adding another.
+    pub fn submit(&mut self, mode: &str, difficulty: Difficulty, entry: ScoreEntry) -> Option<usize> {
+        let board = self.boards.entry((mode.to_string(), difficulty)).or_default();
+        let same_run = board
+            .iter()
+            .position(|e| !entry.started.is_empty() && e.started == entry.started);
+        match same_run {
+            Some(i) if board[i].score > entry.score => return None,
+            Some(i) => {
+                board.remove(i);
+            }
+            None => (),
+        }
+        // Below a tie, the run that got there first stays ahead.
+        let rank = board.iter().position(|e| e.score < entry.score).unwrap_or(board.len());
+        if rank >= BOARD_SIZE {
+            return None;
+        }
+        board.insert(rank, entry);
+        board.truncate(BOARD_SIZE);
+        Some(rank)
+    }
+
+    /// Puts every run from `other` on the boards here, the same as if
+    /// they'd been played on this machine, so each board ends up with
+    /// the best of both. Returns how many made it on.
+    pub fn merge(&mut self, other: &HighScores) -> usize {
+        let mut added = 0;
+        for ((mode, difficulty), entries) in &other.boards {
+            for entry in entries {
+                // Scores from before there were boards can't be told
+                // apart by when they started.
+                if self.board(mode, *difficulty).contains(entry) {
+                    continue;
+                }
+                if self.submit(mode, *difficulty, entry.clone()).is_some() {
+                    added += 1;
+                }
+            }
+        }
+        added
+    }
+
+    pub fn save(&self) {
+        self.save_to(Path::new(SCORES_FILE), Path::new(SCORES_TEMP_FILE));
+    }
+
+    fn save_to(&self, file: &Path, temp: &Path) {
+        let saved = SavedScores {
+            version: SCORES_VERSION,
+            boards: self.boards.clone(),
+        };
+        let result = ron::ser::to_string(&saved)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                fs::write(temp, text)
+                    .and_then(|_| fs::rename(temp, file))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            println!("Can't save high scores to {}: {}", file.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: i32, started: &str) -> ScoreEntry {
+        ScoreEntry {
+            score,
+            started: started.to_string(),
+            ..ScoreEntry::score_only(0)
+        }
+    }
+
+    #[test]
+    fn stale_temp_file_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("onesys-scores-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (file, temp) = (dir.join(SCORES_FILE), dir.join(SCORES_TEMP_FILE));
+
+        let mut scores = HighScores::default();
+        scores.submit("campaign", Difficulty::Normal, entry(120, "a"));
+        scores.save_to(&file, &temp);
+        // A save that died partway through writing.
+        fs::write(&temp, "(version: 1, boards: {").unwrap();
+        assert_eq!(HighScores::load_from(&file).best("campaign", Difficulty::Normal), 120);
+
+        // The next save writes over it.
+        scores.submit("campaign", Difficulty::Normal, entry(200, "b"));
+        scores.save_to(&file, &temp);
+        let loaded = HighScores::load_from(&file);
+        assert_eq!(loaded.board("campaign", Difficulty::Normal), &[entry(200, "b"), entry(120, "a")][..]);
+        assert!(!temp.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn runs_land_on_their_own_board() {
+        let mut scores = HighScores::default();
+        scores.submit("campaign", Difficulty::Hard, entry(50, "a"));
+        scores.submit("campaign", Difficulty::Easy, entry(80, "b"));
+        scores.submit("endless", Difficulty::Hard, entry(30, "c"));
+        assert_eq!(scores.board("campaign", Difficulty::Hard), &[entry(50, "a")][..]);
+        assert_eq!(scores.board("campaign", Difficulty::Easy), &[entry(80, "b")][..]);
+        assert_eq!(scores.board("endless", Difficulty::Hard), &[entry(30, "c")][..]);
+        assert!(scores.board("campaign", Difficulty::Normal).is_empty());
+        assert_eq!(scores.modes(), vec!["campaign", "endless"]);
+    }
+
+    #[test]
+    fn boards_keep_the_best_runs() {
+        let mut scores = HighScores::default();
+        for i in 0..BOARD_SIZE as i32 {
+            assert_eq!(scores.submit("campaign", Difficulty::Normal, entry(i * 10, &i.to_string())), Some(0));
+        }
+        // Too low to make it, then the same score as the last place.
+        assert_eq!(scores.submit("campaign", Difficulty::Normal, entry(-5, "low")), None);
+        assert_eq!(scores.submit("campaign", Difficulty::Normal, entry(0, "tie")), None);
+        assert_eq!(scores.submit("campaign", Difficulty::Normal, entry(45, "mid")), Some(5));
+        let board = scores.board("campaign", Difficulty::Normal);
+        assert_eq!(board.len(), BOARD_SIZE);
+        assert_eq!(board[0].score, 90);
+        assert_eq!(board[BOARD_SIZE - 1].score, 10);
+    }
+
+    #[test]
+    fn a_run_keeps_one_entry() {
+        let mut scores = HighScores::default();
+        scores.submit("campaign", Difficulty::Normal, entry(40, "other"));
+        assert_eq!(scores.submit("campaign", Difficulty::Normal, entry(10, "run")), Some(1));
+        assert_eq!(scores.submit("campaign", Difficulty::Normal, entry(60, "run")), Some(0));
+        // A lower score for the same run leaves its best alone.
+        assert_eq!(scores.submit("campaign", Difficulty::Normal, entry(20, "run")), None);
+        assert_eq!(scores.board("campaign", Difficulty::Normal), &[entry(60, "run"), entry(40, "other")][..]);
+    }
+
+    #[test]
+    fn old_scores_move_onto_normal_boards() {
+        let scores = HighScores::parse("{\"campaign\": 120, \"campaign-timed-180s\": 40}").unwrap();
+        assert_eq!(scores.board("campaign", Difficulty::Normal), &[ScoreEntry::score_only(120)][..]);
+        assert_eq!(scores.best("campaign-timed-180s", Difficulty::Normal), 40);
+        assert_eq!(scores.best("campaign", Difficulty::Hard), 0);
+        assert!(HighScores::parse("(version: 2, boards: {})").is_err());
+    }
+
+    #[test]
+    fn merging_keeps_the_best_of_both() {
+        let mut here = HighScores::default();
+        here.submit("campaign", Difficulty::Normal, entry(50, "a"));
+        here.submit("campaign", Difficulty::Normal, entry(20, "b"));
+        here.submit("campaign", Difficulty::Normal, ScoreEntry::score_only(10));
+        let mut there = HighScores::default();
+        // Run `a` got further before it was copied over.
+        there.submit("campaign", Difficulty::Normal, entry(30, "a"));
+        there.submit("campaign", Difficulty::Normal, entry(40, "c"));
+        there.submit("campaign", Difficulty::Normal, ScoreEntry::score_only(10));
+        there.submit("endless", Difficulty::Hard, entry(5, "d"));
+
+        assert_eq!(here.merge(&there), 2);
+        assert_eq!(
+            here.board("campaign", Difficulty::Normal),
+            &[entry(50, "a"), entry(40, "c"), entry(20, "b"), ScoreEntry::score_only(10)][..]
+        );
+        assert_eq!(here.board("endless", Difficulty::Hard), &[entry(5, "d")][..]);
+        // Merging the same again changes nothing.
+        assert_eq!(here.merge(&there), 0);
+    }
+}
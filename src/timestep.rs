@@ -0,0 +1,77 @@
+//! Turns the time between drawn frames into a whole number of fixed
+//! size updates.
+
+/// What a frame's worth of time came to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Advance {
+    // Updates to run before drawing.
+    pub steps: u32,
+    // Seconds thrown away because they were owed more than the cap.
+    pub dropped: f32,
+}
+
+/// Gathers up time and hands it back in steps of `step` seconds.
+#[derive(Debug)]
+pub struct Timestep {
+    step: f32,
+    max_steps: u32,
+    // Time owed that hasn't made a whole step yet.
+    accumulator: f32,
+}
+
+impl Timestep {
+    pub fn new(step: f32, max_steps: u32) -> Timestep {
+        Timestep {
+            step,
+            max_steps,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Adds `elapsed` seconds and returns how many steps to run for
+    /// them. A long stall would owe more updates than can run before
+    /// the next frame, each one making that frame later still, so
+    /// anything past `max_steps` is dropped instead.
+    pub fn advance(&mut self, elapsed: f32) -> Advance {
+        self.accumulator += elapsed.max(0.0);
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        let dropped = if self.accumulator >= self.step {
+            std::mem::replace(&mut self.accumulator, 0.0)
+        } else {
+            0.0
+        };
+        Advance { steps, dropped }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn time_comes_back_in_whole_steps() {
+        let mut timestep = Timestep::new(STEP, 5);
+        assert_eq!(timestep.advance(STEP * 0.5).steps, 0);
+        assert_eq!(timestep.advance(STEP * 0.75).steps, 1);
+        assert!((timestep.accumulator - STEP * 0.25).abs() < 1e-6);
+        let advance = timestep.advance(STEP * 3.0);
+        assert_eq!(advance, Advance { steps: 3, dropped: 0.0 });
+    }
+
+    #[test]
+    fn a_long_stall_is_capped() {
+        let mut timestep = Timestep::new(STEP, 5);
+        let advance = timestep.advance(10.0);
+        assert_eq!(advance.steps, 5);
+        assert!((advance.dropped - (10.0 - 5.0 * STEP)).abs() < 1e-3);
+        assert_eq!(timestep.accumulator, 0.0);
+        // Back to normal on the next frame.
+        assert_eq!(timestep.advance(STEP).steps, 1);
+    }
+}
@@ -0,0 +1,265 @@
+//! Gameplay options that can be changed in `tunables.ron` without
+//! recompiling. Anything left out of the file keeps its default.
+
+use std::fs;
+use std::io;
+
+use quicksilver::graphics::Color;
+use serde::Deserialize;
+
+pub const TUNABLES_FILE: &str = "tunables.ron";
+
+/// How engine thrust changes the longer it's held.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ThrustCurve {
+    // Full thrust the whole time.
+    Linear,
+    // A strong kick that tapers off to full thrust.
+    Burst,
+    // Starts weak and builds up to full thrust.
+    Ramp,
+}
+
+impl ThrustCurve {
+    /// Multiplier on the engine thrust after it has been held for `held` seconds.
+    pub fn factor(self, held: f32) -> f32 {
+        match self {
+            ThrustCurve::Linear => 1.0,
+            ThrustCurve::Burst => 1.0 + (-held / 0.5).exp(),
+            ThrustCurve::Ramp => 1.0 - 0.75 * (-held / 0.6).exp(),
+        }
+    }
+}
+
+/// How holding fire shoots.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum FireControl {
+    // Keeps firing as fast as the wepons can while held.
+    Auto,
+    // Slows the game and shows an aim line while held,
+    // then fires one shot when let go.
+    HoldToAim,
+}
+
+/// Rule changes for a whole run. Each one scales the score
+/// by how much harder (or easier) it makes things.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct Mutators {
+    // Twice as many rocks every level.
+    pub double_rocks: bool,
+    // The ship stops at the edges instead of wrapping around.
+    pub no_wrap: bool,
+    // Shots fly faster, which makes aiming easier.
+    pub fast_shots: bool,
+    // Rocks, satellites and the wormhole can only be seen close to
+    // the ship or for a moment after a radar pulse finds them.
+    pub fog: bool,
+}
+
+impl Mutators {
+    pub fn score_multiplier(self) -> f32 {
+        let mut multiplier = 1.0;
+        if self.double_rocks {
+            multiplier *= 1.5;
+        }
+        if self.no_wrap {
+            multiplier *= 1.25;
+        }
+        if self.fast_shots {
+            multiplier *= 0.8;
+        }
+        if self.fog {
+            multiplier *= 1.5;
+        }
+        multiplier
+    }
+
+    /// The names of the mutators that are on, for the HUD.
+    pub fn names(self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.double_rocks {
+            names.push("Double rocks");
+        }
+        if self.no_wrap {
+            names.push("No wrap");
+        }
+        if self.fast_shots {
+            names.push("Fast shots");
+        }
+        if self.fog {
+            names.push("Fog");
+        }
+        names
+    }
+}
+
+/// A color the ship and its shots are tinted with.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ShipTint {
+    // The sprites' own colors.
+    None,
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Cyan,
+    Blue,
+    Purple,
+    Pink,
+    // Any other color, as red, green, blue.
+    Custom(u8, u8, u8),
+}
+
+impl ShipTint {
+    /// The color to blend the sprites with, `None` to draw them as they are.
+    pub fn color(self) -> Option<Color> {
+        let (r, g, b) = match self {
+            ShipTint::None => return None,
+            ShipTint::Red => (255, 80, 80),
+            ShipTint::Orange => (255, 160, 60),
+            ShipTint::Yellow => (255, 240, 90),
+            ShipTint::Green => (100, 240, 110),
+            ShipTint::Cyan => (90, 240, 240),
+            ShipTint::Blue => (100, 140, 255),
+            ShipTint::Purple => (190, 110, 255),
+            ShipTint::Pink => (255, 130, 210),
+            ShipTint::Custom(r, g, b) => (r, g, b),
+        };
+        Some(Color::from_rgba(r, g, b, 1.0))
+    }
+}
+
+/// The HUD's color for each system, as red, green, blue.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct HudColors {
+    pub engines: (u8, u8, u8),
+    pub wepons: (u8, u8, u8),
+    pub radar: (u8, u8, u8),
+}
+
+impl Default for HudColors {
+    fn default() -> Self {
+        HudColors {
+            engines: (110, 170, 255),
+            wepons: (255, 110, 110),
+            radar: (110, 235, 140),
+        }
+    }
+}
+
+/// How quickly rocks start coming in endless mode.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct EndlessRamp {
+    // Seconds between rocks at the start of a run.
+    pub start_interval: f32,
+    // The shortest it ever gets between rocks.
+    pub min_interval: f32,
+    // Seconds taken off the gap for every second survived.
+    pub interval_ramp: f32,
+    // No more rocks come in while this many are around.
+    pub max_rocks: usize,
+}
+
+impl Default for EndlessRamp {
+    fn default() -> Self {
+        EndlessRamp {
+            start_interval: 3.0,
+            min_interval: 0.6,
+            interval_ramp: 0.02,
+            max_rocks: 30,
+        }
+    }
+}
+
+impl EndlessRamp {
+    /// Seconds until the next rock, `elapsed` seconds into a run.
+    pub fn spawn_interval(&self, elapsed: f32) -> f32 {
+        (self.start_interval - self.interval_ramp * elapsed).max(self.min_interval)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Tunables {
+    pub thrust_curve: ThrustCurve,
+    // Extra rocks a shot can go through in the piercing wepon mode.
+    pub shot_pierce: u32,
+    // Briefly slow the game down whenever the player switches systems.
+    pub switch_slowmo: bool,
+    // Show a caption for every sound, whether or not it's muted.
+    pub visual_cues: bool,
+    pub fire_control: FireControl,
+    // List things like satellite kills in the bottom left corner.
+    pub ticker: bool,
+    pub mutators: Mutators,
+    pub endless: EndlessRamp,
+    // A ring around the ship pointing at the closest rock or satellite.
+    pub threat_compass: bool,
+    // Let the up arrow thrust while the Wepons system is selected.
+    pub fire_and_thrust: bool,
+    // How much thrust is lost while firing and thrusting, 0 to 1.
+    pub fire_thrust_penalty: f32,
+    pub hud_colors: HudColors,
+    // How many pieces each update's movement is split into, with
+    // collisions checked after each. Raise it if fast shots pass
+    // through rocks.
+    pub physics_substeps: u32,
+    // Seconds before every run ends no matter what, 0 for no limit.
+    pub time_limit: f32,
+    pub ship_tint: ShipTint,
+    // Hold still the effects that grow, spin or flicker, for
+    // players who get sick from motion on screen.
+    pub reduce_motion: bool,
+    // The wormhole only takes the ship if it comes in slowly and
+    // stays inside for a moment, instead of on any touch.
+    pub docking: bool,
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        Tunables {
+            thrust_curve: ThrustCurve::Linear,
+            shot_pierce: 2,
+            switch_slowmo: false,
+            visual_cues: false,
+            fire_control: FireControl::Auto,
+            ticker: true,
+            mutators: Mutators::default(),
+            endless: EndlessRamp::default(),
+            threat_compass: false,
+            fire_and_thrust: false,
+            fire_thrust_penalty: 0.5,
+            hud_colors: HudColors::default(),
+            physics_substeps: 1,
+            time_limit: 0.0,
+            ship_tint: ShipTint::None,
+            reduce_motion: false,
+            docking: false,
+        }
+    }
+}
+
+impl Tunables {
+    pub fn parse(text: &str) -> Result<Tunables, String> {
+        ron::de::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Loads `tunables.ron`, falling back to the defaults if it's
+    /// missing or can't be parsed.
+    pub fn load() -> Tunables {
+        match fs::read_to_string(TUNABLES_FILE) {
+            Ok(text) => Tunables::parse(&text).unwrap_or_else(|e| {
+                println!("Ignoring {}: {}", TUNABLES_FILE, e);
+                Tunables::default()
+            }),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Tunables::default(),
+            Err(e) => {
+                println!("Can't read {}: {}", TUNABLES_FILE, e);
+                Tunables::default()
+            }
+        }
+    }
+}